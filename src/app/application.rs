@@ -1,7 +1,17 @@
-use crate::common::{Settings, EventBus, APPLICATION_SHUTDOWN_COMMAND, Result};
+use crate::common::{Settings, EventBus, APPLICATION_SHUTDOWN_COMMAND, CONFIG_RELOAD_COMMAND, SESSION_SHUTDOWN_REQUEST, Result, RouterError, ScreenResolution, SessionRequest, Metrics};
+use crate::app::{Credentials, PidFile};
 use crate::router::Transport;
+use crate::service::SessionService;
 
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
 use std::thread;
+use std::time::Instant;
+
+static TEST_USERNAME_VAR: &str = "WEBX_USERNAME";
+static TEST_PASSWORD_VAR: &str = "WEBX_PASSWORD";
 
 pub struct Application {
 }
@@ -12,46 +22,216 @@ impl Application {
         }
     }
 
-    pub fn run(&self, settings: &mut Settings) -> Result<()> {
+    pub fn run(&self, settings: Settings, config_path: &str) -> Result<()> {
         info!("Starting WebX Router...");
 
+        // Only a single router can run on a host
+        let pid_file = PidFile::create(settings.pid_file())?;
+
+        let result = self.run_transport(settings, config_path);
+
+        pid_file.remove();
+
+        if result.is_ok() {
+            info!("WebX Router terminated");
+        }
+        result
+    }
+
+    fn run_transport(&self, settings: Settings, config_path: &str) -> Result<()> {
         // Create ZMQ context
         let context = zmq::Context::new();
     
         // Create event bus
-        let event_bus_thread = self.create_event_bus_thread(context.clone());
+        let event_bus_thread = self.create_event_bus_thread(&context)?;
     
         // Create CTRL-C shutdown publisher
-        self.create_shutdown_publisher(&context);
+        let settings = Arc::new(RwLock::new(settings));
+        self.create_shutdown_publisher(&context, settings.clone())?;
+
+        // Create SIGHUP settings reload handler
+        self.create_reload_handler(&context, settings.clone(), config_path)?;
     
         // Create transport
         let transport = Transport::new(context);
     
         info!("WebX Router running");
-        transport.run(settings)?;
+        let result = transport.run(&settings);
     
         // Join event bus thread
         event_bus_thread.join().unwrap();
 
-        info!("WebX Router terminated");
+        result
+    }
+
+    pub fn self_test(&self, settings: &Settings, credentials_path: Option<&str>, is_credentials_from_env: bool) -> Result<()> {
+        info!("Running WebX Router self-test...");
+
+        let context = zmq::Context::new();
+        let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+
+        // Read the test account credentials
+        let credentials = Application::run_test("credentials", || Application::load_test_credentials(settings, credentials_path, is_credentials_from_env))?;
+
+        // Authenticate the test account, create the X11 session and start the engine
+        let request = SessionRequest::new(credentials.username().to_string(), credentials.password().to_string(), ScreenResolution::new(1024, 768), "gb".to_string(), String::new());
+        let session_id = Application::run_test("session creation", || {
+            service.get_or_create_session(settings, &request, "localhost", &context).map(|session| session.id().to_string())
+        })?;
+
+        // Verify the engine responds to ping, always cleaning up the session
+        let ping_result = Application::run_test("engine ping", || service.ping_session(&session_id, settings, &context));
+        let cleanup_result = Application::run_test("cleanup", || service.delete_session(&session_id, settings, &context));
+        ping_result.and(cleanup_result)?;
+
+        info!("WebX Router self-test passed");
+        Ok(())
+    }
+
+    pub fn benchmark(&self, settings: &Settings, session_count: u32, resolution: ScreenResolution, credentials_path: Option<&str>, is_credentials_from_env: bool) -> Result<()> {
+        let context = zmq::Context::new();
+        let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+        let credentials = Application::load_test_credentials(settings, credentials_path, is_credentials_from_env)?;
+        settings.sesman.validate_resolution(&resolution)?;
+
+        // Sessions are created one at a time, as by the Session Proxy, and deleted straight away
+        let mut latencies_ms = Vec::new();
+        for index in 1..=session_count {
+            let request = SessionRequest::new(credentials.username().to_string(), credentials.password().to_string(), resolution, "gb".to_string(), String::new());
+            let start_time = Instant::now();
+            let session_id = service.get_or_create_session(settings, &request, "localhost", &context)
+                .map(|session| session.id().to_string())
+                .map_err(|error| RouterError::SystemError(format!("Benchmark session {} of {} failed: {}", index, session_count, error)))?;
+            let latency_ms = start_time.elapsed().as_millis();
+            latencies_ms.push(latency_ms);
+            println!("Session {} of {} created in {} ms", index, session_count, latency_ms);
+
+            service.delete_session(&session_id, settings, &context)?;
+        }
+
+        if latencies_ms.is_empty() {
+            return Ok(());
+        }
+
+        latencies_ms.sort_unstable();
+        let percentile = |percent: usize| latencies_ms[((latencies_ms.len() * percent + 99) / 100).max(1) - 1];
+        println!("Session creation latency: p50={} ms, p90={} ms, p99={} ms, max={} ms", percentile(50), percentile(90), percentile(99), latencies_ms[latencies_ms.len() - 1]);
+
         Ok(())
     }
 
-    fn create_event_bus_thread(&self, context: zmq::Context) -> thread::JoinHandle<()> {
-        thread::spawn(move ||  {
-            if let Err(error) = EventBus::new(context).run() {
+    pub fn dry_run(&self, settings: &Settings) -> Result<()> {
+        // Settings have already been verified: check the paths that are only used at runtime
+        if !Path::new(&settings.engine.path).is_file() {
+            return Err(RouterError::SystemError(format!("WebX Engine binary {} does not exist", settings.engine.path)));
+        }
+
+        let transport = &settings.transport;
+        println!("Logging: level={}, format={}", settings.logging.level, settings.logging.format());
+        println!("Connector: {}:{}", transport.connector_bind_address(), transport.ports.connector);
+        println!("Publisher: {}:{}", transport.publisher_bind_address(), transport.ports.publisher);
+        println!("Collector: {}:{}", transport.collector_bind_address(), transport.ports.collector);
+        println!("Session: {}:{}", transport.session_bind_address(), transport.ports.session);
+        if let Some(port) = transport.ports.metrics {
+            println!("Metrics: {}:{}", transport.bind_address(), port);
+        }
+        if let Some(port) = transport.ports.health {
+            println!("Health: {}:{}", transport.bind_address(), port);
+        }
+        println!("Message proxy IPC: {}", transport.ipc.message_proxy);
+        println!("Instruction proxy IPC: {}", transport.ipc.instruction_proxy);
+        println!("Engine connector IPC root: {}", transport.ipc.engine_connector_root);
+        println!("Session Manager: enabled={}, IPC={}", settings.sesman.enabled, transport.ipc.sesman_connector);
+        println!("Engine: path={}, logdir={}", settings.engine.path, settings.engine.logdir);
+        println!("Configuration OK");
+
+        Ok(())
+    }
+
+    pub fn dump_config(&self, settings: &Settings) -> Result<()> {
+        // Print the resolved settings (including environment variable overrides) without secrets
+        let mut settings = settings.clone();
+        if !settings.transport.encryption.private.is_empty() {
+            settings.transport.encryption.private = "<redacted>".to_string();
+        }
+
+        let yaml = serde_yaml::to_string(&settings).map_err(|error| RouterError::SystemError(format!("Failed to serialise settings: {}", error)))?;
+        println!("{}", yaml);
+
+        Ok(())
+    }
+
+    fn load_test_credentials(settings: &Settings, credentials_path: Option<&str>, is_credentials_from_env: bool) -> Result<Credentials> {
+        if is_credentials_from_env {
+            Credentials::from_env(TEST_USERNAME_VAR, TEST_PASSWORD_VAR)
+
+        } else {
+            Credentials::from_file(credentials_path.unwrap_or_else(|| settings.sesman.test_credentials_path()))
+        }
+    }
+
+    fn run_test<T, F: FnOnce() -> Result<T>>(name: &str, test: F) -> Result<T> {
+        debug!("Running self-test \"{}\"", name);
+        match test() {
+            Ok(value) => {
+                info!("Self-test \"{}\" passed", name);
+                Ok(value)
+            },
+            Err(error) => Err(RouterError::SystemError(format!("Self-test \"{}\" failed: {}", name, error)))
+        }
+    }
+
+    fn create_event_bus_thread(&self, context: &zmq::Context) -> Result<thread::JoinHandle<()>> {
+        let event_bus = EventBus::new(context)?;
+        Ok(thread::spawn(move ||  {
+            if let Err(error) = event_bus.run() {
                 error!("Event Bus thread error: {}", error);
             }
-        })
+        }))
     }
 
-    fn create_shutdown_publisher(&self, context: &zmq::Context) {
-        let socket = EventBus::create_event_publisher(context).unwrap();
+    fn create_shutdown_publisher(&self, context: &zmq::Context, settings: Arc<RwLock<Settings>>) -> Result<()> {
+        let socket = EventBus::create_event_publisher(context)?;
+        let mut is_shutdown_requested = false;
         ctrlc::set_handler(move || {
-            info!("Sending shutdown command");
-            socket.send(APPLICATION_SHUTDOWN_COMMAND, 0).unwrap();
+            // Give clients time to disconnect first if configured, a second signal shuts down immediately
+            let grace_period_s = settings.read().map_or(0, |settings| settings.sesman.shutdown_grace_period_s);
+            if grace_period_s > 0 && !is_shutdown_requested {
+                info!("Sending graceful shutdown request");
+                is_shutdown_requested = true;
+                socket.send(SESSION_SHUTDOWN_REQUEST, 0).unwrap();
+
+            } else {
+                info!("Sending shutdown command");
+                socket.send(APPLICATION_SHUTDOWN_COMMAND, 0).unwrap();
+            }
 
-        }).expect("Error setting Ctrl-C handler");
+        }).map_err(|error| RouterError::SystemError(format!("Error setting Ctrl-C handler: {}", error)))
     }
-}
 
+    fn create_reload_handler(&self, context: &zmq::Context, settings: Arc<RwLock<Settings>>, config_path: &str) -> Result<()> {
+        let socket = EventBus::create_event_publisher(context)?;
+        let mut signals = Signals::new(&[SIGHUP])?;
+        let config_path = config_path.to_string();
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                info!("Reloading settings");
+                let result = match settings.write() {
+                    Ok(mut settings) => settings.reload(&config_path),
+                    Err(error) => Err(config::ConfigError::Message(error.to_string())),
+                };
+
+                match result {
+                    Ok(_) => {
+                        if let Err(error) = socket.send(CONFIG_RELOAD_COMMAND, 0) {
+                            error!("Failed to send settings reload command: {}", error);
+                        }
+                    },
+                    Err(error) => error!("Failed to reload settings, keeping current ones: {}", error),
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
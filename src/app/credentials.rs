@@ -0,0 +1,49 @@
+use crate::common::{Result, RouterError};
+
+use std::env;
+use std::fs;
+
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+impl Credentials {
+
+    pub fn new(username: String, password: String) -> Result<Self> {
+        if username.is_empty() {
+            return Err(RouterError::AuthenticationError("Username is empty".to_string()));
+        }
+        if password.is_empty() {
+            return Err(RouterError::AuthenticationError(format!("Password for user {} is empty", username)));
+        }
+
+        Ok(Self {
+            username,
+            password,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        // Credentials file contains a single "username:password" line
+        let content = fs::read_to_string(path)?;
+        match content.lines().next().and_then(|line| line.split_once(':')) {
+            Some((username, password)) => Credentials::new(username.to_string(), password.to_string()),
+            None => Err(RouterError::SystemError(format!("Invalid credentials file {}: expected username:password", path)))
+        }
+    }
+
+    pub fn from_env(username_var: &str, password_var: &str) -> Result<Self> {
+        let read_var = |name: &str| env::var(name).map_err(|error| RouterError::AuthenticationError(format!("Failed to read {}: {}", name, error)));
+
+        Credentials::new(read_var(username_var)?, read_var(password_var)?)
+    }
+
+    pub fn username(&self) -> &str {
+        return &self.username;
+    }
+
+    pub fn password(&self) -> &str {
+        return &self.password;
+    }
+}
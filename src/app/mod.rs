@@ -1,3 +1,7 @@
 pub use application::Application;
+pub use credentials::Credentials;
+pub use pid_file::PidFile;
 
-mod application;
\ No newline at end of file
+mod application;
+mod credentials;
+mod pid_file;
\ No newline at end of file
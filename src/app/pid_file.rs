@@ -0,0 +1,40 @@
+use crate::common::{Result, RouterError, System};
+
+use nix::unistd::getpid;
+use std::fs;
+use std::path::Path;
+
+pub struct PidFile {
+    path: String,
+}
+
+impl PidFile {
+
+    pub fn create(path: &str) -> Result<Self> {
+        // Refuse to start if the PID file belongs to a process that is still running
+        if let Ok(content) = fs::read_to_string(path) {
+            if let Ok(process_id) = content.trim().parse::<u32>() {
+                if Path::new(&format!("/proc/{}/status", process_id)).exists() {
+                    return Err(RouterError::SystemError(format!("Another webx-router instance is already running with PID {}", process_id)));
+                }
+            }
+            warn!("Replacing stale PID file {}", path);
+        }
+
+        if let Some(dir) = Path::new(path).parent() {
+            System::create_dir(dir, 0o755)?;
+        }
+        fs::write(path, format!("{}\n", getpid()))?;
+        debug!("Created PID file {}", path);
+
+        Ok(Self {
+            path: path.to_string(),
+        })
+    }
+
+    pub fn remove(&self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {}: {}", self.path, error);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// WebX Router administration tool.
+//
+// Sends privileged commands to the local admin socket of the WebX Router (transport.admin_socket_path).
+// Users need read and write access to the socket file, for example by being members of the webx group.
+use std::fs;
+use std::process;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "webx-router-ctl")]
+struct Opt {
+    /// Path of the WebX Router admin socket
+    #[structopt(short, long, default_value = "/run/webx/router.sock")]
+    socket: String,
+
+    /// Path of the WebX Router session monitor socket
+    #[structopt(long, default_value = "/run/webx/monitor.sock")]
+    monitor_socket: String,
+
+    /// Time to wait for a response from the WebX Router
+    #[structopt(long, default_value = "5000")]
+    timeout_ms: i32,
+
+    /// Number of attempts to send the command before failing
+    #[structopt(long, default_value = "1")]
+    retry: u32,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Stop the WebX Router and all of its sessions
+    Shutdown,
+    /// Stop a session and its WebX Engine
+    KillSession {
+        session_id: String,
+    },
+    /// Reload the WebX Router configuration
+    ReloadConfig,
+    /// List the running sessions
+    ListSessions,
+    /// Print the WebX Engine logs of a session
+    EngineLogs {
+        session_id: String,
+    },
+    /// Print session lifecycle events as they occur (until interrupted)
+    Monitor,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    // Monitoring subscribes to events rather than sending a request
+    if let Command::Monitor = opt.command {
+        if let Err(error) = monitor(&opt.monitor_socket) {
+            eprintln!("Failed to monitor sessions on {}: {}", opt.monitor_socket, error);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let request = match &opt.command {
+        Command::Shutdown => "shutdown".to_string(),
+        Command::KillSession { session_id } => format!("delete,{}", session_id),
+        Command::ReloadConfig => "reload".to_string(),
+        Command::ListSessions => "list".to_string(),
+        Command::EngineLogs { session_id } => format!("engine_logs,{}", session_id),
+        Command::Monitor => unreachable!(),
+    };
+
+    let response = match send_request_with_retry(&opt.socket, &request, opt.timeout_ms, opt.retry) {
+        Ok(response) => response,
+        Err(error) => {
+            eprintln!("Failed to send request to {}: {}", opt.socket, error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(message) = check_response(&response) {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+
+    match opt.command {
+        Command::EngineLogs { .. } => print_logs(&response),
+        _ => println!("{}", response),
+    }
+}
+
+fn check_response(response: &str) -> Result<&str, &str> {
+    // Errors are returned as 1,<message>
+    match response.strip_prefix("1,") {
+        Some(message) => Err(message),
+        None => Ok(response),
+    }
+}
+
+fn send_request_with_retry(path: &str, request: &str, timeout_ms: i32, attempts: u32) -> Result<String, String> {
+    let context = zmq::Context::new();
+    let mut attempt = 1;
+    loop {
+        // A REQ socket cannot be reused after a failed receive so each attempt uses a new one
+        match send_request(&context, path, request, timeout_ms) {
+            Ok(response) => return Ok(response),
+            Err(error) if attempt >= attempts => return Err(error),
+            Err(error) => eprintln!("Attempt {} of {} failed: {}", attempt, attempts, error),
+        }
+        attempt += 1;
+    }
+}
+
+fn send_request(context: &zmq::Context, path: &str, request: &str, timeout_ms: i32) -> Result<String, String> {
+    let socket = context.socket(zmq::REQ).map_err(|error| error.to_string())?;
+    socket.set_linger(0).map_err(|error| error.to_string())?;
+    socket.set_sndtimeo(timeout_ms).map_err(|error| error.to_string())?;
+    socket.set_rcvtimeo(timeout_ms).map_err(|error| error.to_string())?;
+    socket.connect(&format!("ipc://{}", path)).map_err(|error| error.to_string())?;
+
+    socket.send(request, 0).map_err(|error| error.to_string())?;
+    match socket.recv_string(0) {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(_)) => Err("response is not valid UTF-8".to_string()),
+        Err(zmq::Error::EAGAIN) => Err("no response from the WebX Router".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn print_logs(response: &str) {
+    // The response contains one log path per line
+    for path in response.lines() {
+        println!("==> {} <==", path);
+        match fs::read_to_string(path) {
+            Ok(content) => print!("{}", content),
+            Err(error) => eprintln!("Failed to read {}: {}", path, error),
+        }
+    }
+}
+
+fn monitor(path: &str) -> Result<(), String> {
+    let context = zmq::Context::new();
+    let socket = context.socket(zmq::SUB).map_err(|error| error.to_string())?;
+    socket.set_linger(0).map_err(|error| error.to_string())?;
+    socket.set_subscribe(b"").map_err(|error| error.to_string())?;
+    socket.connect(&format!("ipc://{}", path)).map_err(|error| error.to_string())?;
+
+    // Runs until the process is interrupted
+    loop {
+        let event = match socket.recv_string(0) {
+            Ok(Ok(event)) => event,
+            Ok(Err(_)) => continue,
+            Err(error) => return Err(error.to_string()),
+        };
+
+        // Events are received as <timestamp>,<event>,<sessionId>,<username>
+        let parts = event.splitn(4, ',').collect::<Vec<&str>>();
+        if parts.len() == 4 {
+            println!("[{}] {} {} {}", parts[0], parts[1].to_uppercase(), parts[2], parts[3]);
+        } else {
+            println!("{}", event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_kill_session_is_an_error() {
+        assert_eq!(check_response("1,SessionError: Session not found with id unknown"), Err("SessionError: Session not found with id unknown"));
+    }
+
+    #[test]
+    fn successful_kill_session_is_not_an_error() {
+        assert_eq!(check_response("ok"), Ok("ok"));
+    }
+}
@@ -0,0 +1,86 @@
+use nix::unistd::{getgrouplist, Group, Uid, User};
+use std::ffi::CString;
+
+pub struct Account {
+    username: String,
+    uid: u32,
+    groups: Vec<u32>,
+}
+
+impl Account {
+
+    pub fn from_user(user: User) -> Option<Account> {
+        if user.dir.as_os_str().is_empty() {
+            error!("User {} has no home directory", user.name);
+            return None;
+        }
+
+        // Get the supplementary groups of the user (including the primary group)
+        let groups = match CString::new(user.name.as_str()) {
+            Ok(username) => match getgrouplist(&username, user.gid) {
+                Ok(groups) => groups.iter().map(|gid| gid.as_raw()).collect(),
+                Err(error) => {
+                    error!("Failed to get groups for user {}: {}", user.name, error);
+                    return None;
+                }
+            },
+            Err(_) => return None,
+        };
+
+        Some(Account {
+            username: user.name,
+            uid: user.uid.as_raw(),
+            groups,
+        })
+    }
+
+    pub fn from_uid(uid: u32) -> Option<Account> {
+        match User::from_uid(Uid::from_raw(uid)) {
+            Ok(Some(user)) => Account::from_user(user),
+            Ok(None) => None,
+            Err(error) => {
+                error!("Failed to get user with uid {}: {}", uid, error);
+                None
+            }
+        }
+    }
+
+    pub fn from_username(username: &str) -> Option<Account> {
+        match User::from_name(username) {
+            Ok(Some(user)) => Account::from_user(user),
+            Ok(None) => None,
+            Err(error) => {
+                error!("Failed to get user {}: {}", username, error);
+                None
+            }
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        return &self.username;
+    }
+
+    pub fn uid(&self) -> u32 {
+        return self.uid;
+    }
+
+    pub fn groups(&self) -> &[u32] {
+        return &self.groups;
+    }
+
+    pub fn is_member_of_any(&self, groups: &[String]) -> bool {
+        groups.iter().any(|group_name| {
+            match Group::from_name(group_name) {
+                Ok(Some(group)) => self.groups().contains(&group.gid.as_raw()),
+                Ok(None) => {
+                    warn!("Group {} does not exist", group_name);
+                    false
+                },
+                Err(error) => {
+                    warn!("Failed to get group {}: {}", group_name, error);
+                    false
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,126 @@
+use crate::common::{Result, RouterError, ResourceLimitSettings};
+
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::{close, write};
+use std::ffi::CString;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+static CGROUP_ROOT: &str = "/sys/fs/cgroup";
+static PROC_SELF_CGROUP: &str = "/proc/self/cgroup";
+// Leaf cgroup of the router process inside its own (delegated) cgroup
+static ROUTER_CGROUP_NAME: &str = "webx-router";
+static CGROUP_CONTROLLERS: &str = "+cpu +memory +pids";
+static CPU_PERIOD_US: u64 = 100000;
+
+pub struct CgroupV2 {
+    path: PathBuf,
+}
+
+impl CgroupV2 {
+
+    // An existing cgroup (for example of a restored session) is reused with the new limits
+    pub fn create(name: &str, limits: &ResourceLimitSettings) -> Result<Self> {
+        // The controllers must be enabled in the parent for the limit files to exist
+        let parent_path = CgroupV2::delegated_root()?;
+        CgroupV2::write_file(&parent_path.join("cgroup.subtree_control"), CGROUP_CONTROLLERS)?;
+
+        let path = parent_path.join(name);
+        if let Err(error) = fs::create_dir(&path) {
+            if error.kind() != ErrorKind::AlreadyExists {
+                return Err(RouterError::SystemError(format!("Could not create cgroup {}: {}", path.display(), error)));
+            }
+        }
+
+        let cgroup = Self {
+            path,
+        };
+
+        // 100% corresponds to one CPU
+        if let Some(max_cpu_percent) = limits.max_cpu_percent {
+            cgroup.write("cpu.max", &format!("{} {}", max_cpu_percent as u64 * CPU_PERIOD_US / 100, CPU_PERIOD_US))?;
+        }
+        if let Some(max_memory_mb) = limits.max_memory_mb {
+            cgroup.write("memory.max", &(max_memory_mb * 1024 * 1024).to_string())?;
+        }
+        if let Some(max_pids) = limits.max_pids {
+            cgroup.write("pids.max", &max_pids.to_string())?;
+        }
+
+        debug!("Created cgroup {}", cgroup.path.display());
+        Ok(cgroup)
+    }
+
+    pub fn add_process(&self, process_id: u32) -> Result<()> {
+        self.write("cgroup.procs", &process_id.to_string())
+    }
+
+    // Moves the spawned process to the cgroup before it executes, so that the processes it forks are also limited
+    pub fn add_command(&self, command: &mut Command) -> Result<()> {
+        let procs_path = self.path.join("cgroup.procs");
+        let procs_path = CString::new(procs_path.to_string_lossy().as_bytes())
+            .map_err(|error| RouterError::SystemError(format!("Invalid cgroup path {}: {}", procs_path.display(), error)))?;
+
+        // Only async-signal-safe calls are made between fork and exec: writing 0 moves the calling process
+        unsafe {
+            command.pre_exec(move || {
+                let file_descriptor = open(procs_path.as_c_str(), OFlag::O_WRONLY, Mode::empty())?;
+                let result = write(file_descriptor, b"0");
+                let _ = close(file_descriptor);
+                result?;
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&self) {
+        // Only possible once all the processes of the cgroup have exited
+        match fs::remove_dir(&self.path) {
+            Ok(_) => debug!("Removed cgroup {}", self.path.display()),
+            Err(error) => warn!("Could not remove cgroup {}: {}", self.path.display(), error),
+        }
+    }
+
+    fn delegated_root() -> Result<PathBuf> {
+        // The cgroup v2 entry of the router is "0::<path>", delegated to the router by systemd with Delegate=yes
+        let content = fs::read_to_string(PROC_SELF_CGROUP)?;
+        let own_path = match content.lines().find_map(|line| line.strip_prefix("0::")) {
+            Some(own_path) => Path::new(CGROUP_ROOT).join(own_path.trim_start_matches('/')),
+            None => return Err(RouterError::SystemError(format!("No cgroup v2 entry found in {}", PROC_SELF_CGROUP)))
+        };
+
+        // The router has already moved itself to its leaf cgroup
+        if own_path.file_name().map_or(false, |name| name == ROUTER_CGROUP_NAME) {
+            return match own_path.parent() {
+                Some(parent_path) => Ok(parent_path.to_path_buf()),
+                None => Err(RouterError::SystemError(format!("Invalid router cgroup {}", own_path.display())))
+            };
+        }
+
+        // A cgroup containing processes cannot enable controllers for its children: the router moves to a leaf cgroup first
+        let router_path = own_path.join(ROUTER_CGROUP_NAME);
+        if let Err(error) = fs::create_dir(&router_path) {
+            if error.kind() != ErrorKind::AlreadyExists {
+                return Err(RouterError::SystemError(format!("Could not create cgroup {}: {}", router_path.display(), error)));
+            }
+        }
+        CgroupV2::write_file(&router_path.join("cgroup.procs"), "0")?;
+        debug!("Moved WebX Router to cgroup {}", router_path.display());
+
+        Ok(own_path)
+    }
+
+    fn write(&self, file_name: &str, value: &str) -> Result<()> {
+        CgroupV2::write_file(&self.path.join(file_name), value)
+    }
+
+    fn write_file(path: &Path, value: &str) -> Result<()> {
+        fs::write(path, value).map_err(|error| RouterError::SystemError(format!("Could not write \"{}\" to {}: {}", value, path.display(), error)))
+    }
+}
@@ -1,24 +1,90 @@
 use std::process::Child;
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineStatus {
+    Running,
+    // The process is running but does not respond to requests
+    Unresponsive,
+    Stopped,
+}
+
+impl fmt::Display for EngineStatus {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            EngineStatus::Running => "running",
+            EngineStatus::Unresponsive => "unresponsive",
+            EngineStatus::Stopped => "stopped",
+        };
+        write!(formatter, "{}", status)
+    }
+}
 
 pub struct Engine {
-    process: Child,
+    // Engines recovered after a router restart are not child processes of the router
+    process: Option<Child>,
+    process_id: u32,
     ipc: String,
+    log_paths: Vec<String>,
+    version: Option<String>,
 }
 
 impl Engine {
 
-    pub fn new(process: Child, ipc: String) -> Self {
+    pub fn new(process: Child, ipc: String, log_paths: Vec<String>) -> Self {
+        let process_id = process.id();
         Self {
-            process,
+            process: Some(process),
+            process_id,
             ipc,
+            log_paths,
+            version: None,
         }
     }
 
-    pub fn process(&mut self) -> &mut Child {
-        return &mut self.process;
+    pub fn recovered(process_id: u32, ipc: String, log_paths: Vec<String>) -> Self {
+        Self {
+            process: None,
+            process_id,
+            ipc,
+            log_paths,
+            version: None,
+        }
+    }
+
+    pub fn process(&mut self) -> Option<&mut Child> {
+        return self.process.as_mut();
+    }
+
+    pub fn process_id(&self) -> u32 {
+        return self.process_id;
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        match &mut self.process {
+            Some(process) => matches!(process.try_wait(), Ok(None)),
+            // Recovered engines cannot be waited for: check that the process still exists
+            None => kill(Pid::from_raw(self.process_id as i32), None).is_ok(),
+        }
     }
 
     pub fn ipc(&self) -> &str {
         return &self.ipc;
     }
+
+    pub fn log_paths(&self) -> &[String] {
+        return &self.log_paths;
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        return self.version.as_deref();
+    }
+
+    pub fn set_version(&mut self, version: Option<String>) {
+        self.version = version;
+    }
 }
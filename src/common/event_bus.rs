@@ -1,32 +1,35 @@
-use crate::common::{Result};
-use std::process;
+use crate::common::{Result, RouterError};
 
 static EVENT_BUS_SUB_ADDR: &str = "inproc://event-bus/subscriber";
 static EVENT_BUS_PUB_ADDR: &str = "inproc://event-bus/publisher";
 
 pub static INPROC_APP_TOPIC: &str = "app";
 pub static INPROC_SESSION_TOPIC: &str = "session";
+pub static INPROC_CONFIG_TOPIC: &str = "config";
 
 pub static APPLICATION_SHUTDOWN_COMMAND: &str = "app:shutdown";
+pub static CONFIG_RELOAD_COMMAND: &str = "config:reload";
+pub static SESSION_EXPIRING_EVENT: &str = "session:expiring";
+pub static SESSION_SHUTDOWN_REQUEST: &str = "session:shutdown_request";
 
 pub struct EventBus {
-    context: zmq::Context
+    xsub_socket: zmq::Socket,
+    xpub_socket: zmq::Socket,
 }
 
 impl EventBus {
 
-    pub fn new(context: zmq::Context) -> Self {
-        Self {
-            context
-        }
+    // The proxy sockets are bound on creation so that failures are reported before the event bus runs
+    pub fn new(context: &zmq::Context) -> Result<Self> {
+        Ok(Self {
+            xsub_socket: EventBus::create_proxy_subscriber(context)?,
+            xpub_socket: EventBus::create_proxy_publisher(context)?,
+        })
     }
 
     pub fn run(&self) -> Result<()> {
-        // Create proxy subcriber
-        let xsub_socket = self.create_proxy_subscriber(&self.context).unwrap();
-
-        // Create proxy publisher
-        let xpub_socket = self.create_proxy_publisher(&self.context).unwrap();
+        let xsub_socket = &self.xsub_socket;
+        let xpub_socket = &self.xpub_socket;
 
         let mut running = true;
         while running {
@@ -56,24 +59,22 @@ impl EventBus {
         Ok(())
     }
 
-    fn create_proxy_subscriber(&self, context: &zmq::Context) -> Result<zmq::Socket> {
+    fn create_proxy_subscriber(context: &zmq::Context) -> Result<zmq::Socket> {
         let socket = context.socket(zmq::SUB)?;
         socket.set_subscribe(b"")?;
         socket.set_linger(0)?;
         if let Err(error) = socket.bind(EVENT_BUS_SUB_ADDR) {
-            error!("Failed to bind event bus XSUB to {}: {}", EVENT_BUS_SUB_ADDR, error);
-            process::exit(1);
+            return Err(RouterError::TransportError(format!("Failed to bind event bus XSUB to {}: {}", EVENT_BUS_SUB_ADDR, error)));
         }
 
         Ok(socket)
     }
 
-    fn create_proxy_publisher(&self, context: &zmq::Context) -> Result<zmq::Socket> {
+    fn create_proxy_publisher(context: &zmq::Context) -> Result<zmq::Socket> {
         let socket = context.socket(zmq::PUB)?;
         socket.set_linger(0)?;
         if let Err(error) = socket.bind(EVENT_BUS_PUB_ADDR) {
-            error!("Failed to bind event bus XPUB to {}: {}", EVENT_BUS_PUB_ADDR, error);
-            process::exit(1);
+            return Err(RouterError::TransportError(format!("Failed to bind event bus XPUB to {}: {}", EVENT_BUS_PUB_ADDR, error)));
         }
 
         Ok(socket)
@@ -84,8 +85,7 @@ impl EventBus {
         socket.set_linger(0)?;
 
         if let Err(error) = socket.connect(EVENT_BUS_SUB_ADDR) {
-            error!("Failed to connect inproc event publisher to {}: {}", EVENT_BUS_SUB_ADDR, error);
-            process::exit(1);
+            return Err(RouterError::TransportError(format!("Failed to connect inproc event publisher to {}: {}", EVENT_BUS_SUB_ADDR, error)));
         }
 
         Ok(socket)
@@ -103,8 +103,7 @@ impl EventBus {
         socket.set_linger(0)?;
 
         if let Err(error) = socket.connect(EVENT_BUS_PUB_ADDR) {
-            error!("Failed to connect inproc event subscriber to {}: {}", EVENT_BUS_PUB_ADDR, error);
-            process::exit(1);
+            return Err(RouterError::TransportError(format!("Failed to connect inproc event subscriber to {}: {}", EVENT_BUS_PUB_ADDR, error)));
         }
 
         Ok(socket)
@@ -0,0 +1,44 @@
+use crate::common::{Result, RouterError};
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+pub struct IpFilter {
+    allowed_networks: Vec<IpNet>,
+    blocked_networks: Vec<IpNet>,
+}
+
+impl IpFilter {
+
+    pub fn new(allowed_ips: &[String], blocked_ips: &[String]) -> Result<Self> {
+        Ok(Self {
+            allowed_networks: IpFilter::parse_networks(allowed_ips)?,
+            blocked_networks: IpFilter::parse_networks(blocked_ips)?,
+        })
+    }
+
+    pub fn is_allowed(&self, address: &str) -> bool {
+        let address = match address.parse::<IpAddr>() {
+            Ok(address) => address,
+            // Only filter when rules are defined
+            Err(_) => return self.allowed_networks.is_empty() && self.blocked_networks.is_empty(),
+        };
+
+        if self.blocked_networks.iter().any(|network| network.contains(&address)) {
+            return false;
+        }
+
+        self.allowed_networks.is_empty() || self.allowed_networks.iter().any(|network| network.contains(&address))
+    }
+
+    fn parse_networks(values: &[String]) -> Result<Vec<IpNet>> {
+        values.iter().map(|value| {
+            // Accept both CIDR notation and single addresses
+            match (value.parse::<IpNet>(), value.parse::<IpAddr>()) {
+                (Ok(network), _) => Ok(network),
+                (_, Ok(address)) => Ok(IpNet::from(address)),
+                _ => Err(RouterError::SystemError(format!("Invalid IP address or network \"{}\"", value))),
+            }
+        }).collect()
+    }
+}
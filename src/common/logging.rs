@@ -0,0 +1,63 @@
+use crate::common::LoggingSettings;
+
+use env_logger::Env;
+use std::cell::RefCell;
+use std::io::Write;
+
+thread_local! {
+    static CURRENT_SESSION_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+// Associates a session id with all logs of the current thread until dropped
+pub struct SessionLogContext {
+    previous_session_id: Option<String>,
+}
+
+impl SessionLogContext {
+
+    pub fn new(session_id: &str) -> Self {
+        let previous_session_id = CURRENT_SESSION_ID.with(|current| current.replace(Some(session_id.to_string())));
+        Self {
+            previous_session_id,
+        }
+    }
+}
+
+impl Drop for SessionLogContext {
+    fn drop(&mut self) {
+        let previous_session_id = self.previous_session_id.take();
+        CURRENT_SESSION_ID.with(|current| current.replace(previous_session_id));
+    }
+}
+
+pub struct Logging {
+}
+
+impl Logging {
+
+    pub fn init(settings: &LoggingSettings) {
+        let env = Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, &settings.level);
+        let mut builder = env_logger::Builder::from_env(env);
+
+        if settings.format() == "json" {
+            builder.format(|buf, record| {
+                let mut line = serde_json::json!({
+                    "timestamp": buf.timestamp_millis().to_string(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+
+                CURRENT_SESSION_ID.with(|current| {
+                    if let Some(session_id) = current.borrow().as_ref() {
+                        line["session_id"] = serde_json::Value::from(session_id.as_str());
+                    }
+                });
+
+                writeln!(buf, "{}", line)
+            });
+        }
+
+        builder.init();
+    }
+}
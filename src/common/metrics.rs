@@ -0,0 +1,116 @@
+use crate::common::System;
+
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ENGINE_PING_LATENCY_BUCKETS_MS: [f64; 9] = [1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+struct Histogram {
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+pub struct Metrics {
+    sessions_active: AtomicU64,
+    sessions_created_total: AtomicU64,
+    sessions_failed_total: AtomicU64,
+    engine_ping_latency_ms: Mutex<Histogram>,
+    relay_messages_forwarded_total: AtomicU64,
+    instruction_messages_forwarded_total: AtomicU64,
+    session_proxy_heartbeat_s: AtomicU64,
+}
+
+impl Metrics {
+
+    pub fn new() -> Self {
+        Self {
+            sessions_active: AtomicU64::new(0),
+            sessions_created_total: AtomicU64::new(0),
+            sessions_failed_total: AtomicU64::new(0),
+            engine_ping_latency_ms: Mutex::new(Histogram {
+                bucket_counts: vec![0; ENGINE_PING_LATENCY_BUCKETS_MS.len()],
+                sum: 0.0,
+                count: 0,
+            }),
+            relay_messages_forwarded_total: AtomicU64::new(0),
+            instruction_messages_forwarded_total: AtomicU64::new(0),
+            session_proxy_heartbeat_s: AtomicU64::new(System::current_time_s()),
+        }
+    }
+
+    pub fn set_sessions_active(&self, count: usize) {
+        self.sessions_active.store(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn sessions_active(&self) -> u64 {
+        self.sessions_active.load(Ordering::Relaxed)
+    }
+
+    pub fn update_session_proxy_heartbeat(&self) {
+        self.session_proxy_heartbeat_s.store(System::current_time_s(), Ordering::Relaxed);
+    }
+
+    pub fn session_proxy_heartbeat_age_s(&self) -> u64 {
+        System::current_time_s().saturating_sub(self.session_proxy_heartbeat_s.load(Ordering::Relaxed))
+    }
+
+    pub fn inc_sessions_created(&self) {
+        self.sessions_created_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_sessions_failed(&self) {
+        self.sessions_failed_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_relay_messages_forwarded(&self) {
+        self.relay_messages_forwarded_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_instruction_messages_forwarded(&self) {
+        self.instruction_messages_forwarded_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_engine_ping_latency_ms(&self, latency_ms: f64) {
+        if let Ok(mut histogram) = self.engine_ping_latency_ms.lock() {
+            for (index, bucket) in ENGINE_PING_LATENCY_BUCKETS_MS.iter().enumerate() {
+                if latency_ms <= *bucket {
+                    histogram.bucket_counts[index] += 1;
+                }
+            }
+            histogram.sum += latency_ms;
+            histogram.count += 1;
+        }
+    }
+
+    // Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        Metrics::render_value(&mut output, "webx_sessions_active", "gauge", "Number of active WebX sessions", self.sessions_active.load(Ordering::Relaxed));
+        Metrics::render_value(&mut output, "webx_sessions_created_total", "counter", "Total number of WebX sessions created", self.sessions_created_total.load(Ordering::Relaxed));
+        Metrics::render_value(&mut output, "webx_sessions_failed_total", "counter", "Total number of failed WebX session creations", self.sessions_failed_total.load(Ordering::Relaxed));
+        Metrics::render_value(&mut output, "webx_relay_messages_forwarded_total", "counter", "Total number of engine messages forwarded to the relay", self.relay_messages_forwarded_total.load(Ordering::Relaxed));
+        Metrics::render_value(&mut output, "webx_instruction_messages_forwarded_total", "counter", "Total number of relay instructions forwarded to the engines", self.instruction_messages_forwarded_total.load(Ordering::Relaxed));
+
+        if let Ok(histogram) = self.engine_ping_latency_ms.lock() {
+            let _ = writeln!(output, "# HELP webx_engine_ping_latency_ms Latency of WebX Engine pings in milliseconds");
+            let _ = writeln!(output, "# TYPE webx_engine_ping_latency_ms histogram");
+            for (index, bucket) in ENGINE_PING_LATENCY_BUCKETS_MS.iter().enumerate() {
+                let _ = writeln!(output, "webx_engine_ping_latency_ms_bucket{{le=\"{}\"}} {}", bucket, histogram.bucket_counts[index]);
+            }
+            let _ = writeln!(output, "webx_engine_ping_latency_ms_bucket{{le=\"+Inf\"}} {}", histogram.count);
+            let _ = writeln!(output, "webx_engine_ping_latency_ms_sum {}", histogram.sum);
+            let _ = writeln!(output, "webx_engine_ping_latency_ms_count {}", histogram.count);
+        }
+
+        output
+    }
+
+    fn render_value(output: &mut String, name: &str, metric_type: &str, help: &str, value: u64) {
+        let _ = writeln!(output, "# HELP {} {}", name, help);
+        let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+        let _ = writeln!(output, "{} {}", name, value);
+    }
+}
@@ -1,17 +1,31 @@
-pub use event_bus::{EventBus, APPLICATION_SHUTDOWN_COMMAND, INPROC_APP_TOPIC, INPROC_SESSION_TOPIC};
+pub use event_bus::{EventBus, APPLICATION_SHUTDOWN_COMMAND, CONFIG_RELOAD_COMMAND, SESSION_EXPIRING_EVENT, SESSION_SHUTDOWN_REQUEST, INPROC_APP_TOPIC, INPROC_SESSION_TOPIC, INPROC_CONFIG_TOPIC};
 pub use error::{RouterError, Result};
-pub use settings::{Settings, TransportSettings, EncryptionSettings, PortSettings, IPCSettings};
+pub use settings::{Settings, LoggingSettings, TransportSettings, EncryptionSettings, PortSettings, IPCSettings, HealthSettings, EngineSettings, RetryPolicy, HookSettings, ResourceLimitSettings, KeepaliveSettings};
 pub use system::System;
 pub use session::Session;
+pub use session_request::SessionRequest;
 pub use session_container::SessionContainer;
-pub use engine::Engine;
+pub use engine::{Engine, EngineStatus};
 pub use x11_session::X11Session;
+pub use screen_resolution::ScreenResolution;
+pub use account::Account;
+pub use metrics::Metrics;
+pub use logging::{Logging, SessionLogContext};
+pub use ip_filter::IpFilter;
+pub use cgroup::CgroupV2;
 
 mod event_bus;
 mod error;
 mod settings;
 mod system;
 mod session;
+mod session_request;
 mod session_container;
 mod engine;
-mod x11_session;
\ No newline at end of file
+mod x11_session;
+mod screen_resolution;
+mod account;
+mod metrics;
+mod logging;
+mod ip_filter;
+mod cgroup;
\ No newline at end of file
@@ -0,0 +1,95 @@
+use crate::common::{Result, RouterError};
+
+use std::fmt;
+use std::str::FromStr;
+
+static MAX_DIMENSION: u32 = 16384;
+
+static PRESETS: [(&str, u32, u32); 8] = [
+    ("hd", 1280, 720),
+    ("wxga", 1366, 768),
+    ("sxga", 1280, 1024),
+    ("hd+", 1600, 900),
+    ("fhd", 1920, 1080),
+    ("wuxga", 1920, 1200),
+    ("qhd", 2560, 1440),
+    ("4k", 3840, 2160),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenResolution {
+    width: u32,
+    height: u32,
+}
+
+impl ScreenResolution {
+
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+        }
+    }
+
+    pub fn from_preset_name(name: &str) -> Result<Self> {
+        let name = name.to_lowercase();
+        let presets = ScreenResolution::standard_presets();
+        match presets.iter().find(|(preset_name, _)| *preset_name == name) {
+            Some((_, resolution)) => Ok(*resolution),
+            None => {
+                let preset_names = presets.iter().map(|(preset_name, _)| preset_name.as_str()).collect::<Vec<&str>>();
+                Err(RouterError::SessionError(format!("Unknown screen resolution preset \"{}\" (available presets: {})", name, preset_names.join(", "))))
+            }
+        }
+    }
+
+    pub fn standard_presets() -> Vec<(String, ScreenResolution)> {
+        PRESETS.iter()
+            .map(|(name, width, height)| (name.to_string(), ScreenResolution::new(*width, *height)))
+            .collect()
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.height;
+    }
+
+    pub fn validate(&self, min_width: u32, min_height: u32, max_width: u32, max_height: u32) -> Result<()> {
+        if self.width > max_width || self.height > max_height {
+            return Err(RouterError::SessionError(format!("Screen resolution {} exceeds the maximum of {}x{}", self, max_width, max_height)));
+        }
+        if self.width < min_width || self.height < min_height {
+            return Err(RouterError::SessionError(format!("Screen resolution {} is below the minimum of {}x{}", self, min_width, min_height)));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ScreenResolution {
+    type Err = RouterError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let parts = value.trim().split(|c| c == 'x' || c == 'X').collect::<Vec<&str>>();
+        if parts.len() != 2 {
+            return Err(RouterError::SessionError(format!("Invalid screen resolution \"{}\": expected WIDTHxHEIGHT", value)));
+        }
+
+        let width = parts[0].parse::<u32>()?;
+        let height = parts[1].parse::<u32>()?;
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(RouterError::SessionError(format!("Screen resolution \"{}\" is out of range", value)));
+        }
+
+        Ok(ScreenResolution::new(width, height))
+    }
+}
+
+impl fmt::Display for ScreenResolution {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}x{}", self.width, self.height)
+    }
+}
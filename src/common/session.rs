@@ -1,30 +1,115 @@
 use std::fs;
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::common::{Engine, X11Session, System};
+use crate::common::{Engine, X11Session, System, ScreenResolution, CgroupV2};
 
 use signal_child::Signalable;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+
+static RECOVERED_ENGINE_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Session {
     x11_session: X11Session,
     engine: Engine,
+    resolution: ScreenResolution,
+    keyboard: String,
+    tenant: String,
+    engine_restarts: u32,
+    created_at: u64,
+    is_expiry_warning_sent: bool,
+    is_suspended: bool,
+    connection_count: u32,
+    recording: Option<Child>,
+    cgroup: Option<CgroupV2>,
     last_activity: u64,
 }
 
 impl Session {
 
-    pub fn new(x11_session: X11Session, engine: Engine) -> Self {
+    pub fn new(x11_session: X11Session, engine: Engine, resolution: ScreenResolution, keyboard: &str, tenant: &str) -> Self {
         Self {
             x11_session,
             engine,
+            resolution,
+            keyboard: keyboard.to_string(),
+            tenant: tenant.to_string(),
+            engine_restarts: 0,
+            created_at: System::current_time_s(),
+            is_expiry_warning_sent: false,
+            is_suspended: false,
+            connection_count: 0,
+            recording: None,
+            cgroup: None,
             last_activity: System::current_time_s()
         }
     }
 
+    #[cfg(test)]
+    pub fn for_test(session_id: &str, username: &str, uid: u32, display_id: &str) -> Self {
+        // A short-lived process stands in for the WebX Engine so that stopping the session has something to stop
+        let process = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let engine = Engine::new(process, format!("/tmp/webx-test-{}.ipc", session_id), vec![]);
+        let x11_session = X11Session::new(session_id.to_string(), username.to_string(), uid, display_id.to_string(), "".to_string());
+        Session::new(x11_session, engine, ScreenResolution::new(1024, 768), "gb", "")
+    }
+
     pub fn is_active(&self, session_inactivity_s: u64) -> bool {
         let current_time = System::current_time_s();
         current_time - self.last_activity <= session_inactivity_s
     }
 
+    pub fn last_activity(&self) -> u64 {
+        return self.last_activity;
+    }
+
+    pub fn created_at(&self) -> u64 {
+        return self.created_at;
+    }
+
+    pub fn set_created_at(&mut self, created_at: u64) {
+        self.created_at = created_at;
+    }
+
+    pub fn duration_s(&self) -> u64 {
+        System::current_time_s().saturating_sub(self.created_at)
+    }
+
+    pub fn is_expiry_warning_sent(&self) -> bool {
+        return self.is_expiry_warning_sent;
+    }
+
+    pub fn set_expiry_warning_sent(&mut self) {
+        self.is_expiry_warning_sent = true;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        return self.is_suspended;
+    }
+
+    pub fn connection_count(&self) -> u32 {
+        return self.connection_count;
+    }
+
+    pub fn add_connection(&mut self) {
+        self.connection_count += 1;
+    }
+
+    pub fn remove_connection(&mut self) {
+        self.connection_count = self.connection_count.saturating_sub(1);
+    }
+
+    pub fn set_suspended(&mut self, is_suspended: bool) {
+        self.is_suspended = is_suspended;
+
+        // The inactivity timer restarts when the session is resumed
+        if !is_suspended {
+            self.update_activity();
+        }
+    }
+
     pub fn update_activity(&mut self) {
         let current_time = System::current_time_s();
         trace!("Updating activity of session {} to {}", self.id(), current_time);
@@ -43,30 +128,127 @@ impl Session {
         return &self.x11_session.username();
     }
 
+    pub fn uid(&self) -> u32 {
+        return self.x11_session.uid();
+    }
+
+    pub fn resolution(&self) -> &ScreenResolution {
+        return &self.resolution;
+    }
+
+    pub fn set_resolution(&mut self, resolution: ScreenResolution) {
+        self.resolution = resolution;
+    }
+
+    pub fn keyboard(&self) -> &str {
+        return &self.keyboard;
+    }
+
+    pub fn tenant(&self) -> &str {
+        return &self.tenant;
+    }
+
     pub fn engine(&self) -> &Engine {
         return &self.engine;
     }
 
-    pub fn stop(&mut self) {
-        let ipc_path = self.engine.ipc().to_string();
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        return &mut self.engine;
+    }
 
-        let process = self.engine.process();
-        let process_id = { process.id() };
-        match process.interrupt() {
-            Ok(_) => {
-                if let Err(error) = process.wait() {
-                    warn!("Failed to wait for WebX Engine for {} running on PID {} to terminate: {}", self.username(), process_id, error);
+    pub fn engine_restarts(&self) -> u32 {
+        return self.engine_restarts;
+    }
 
-                } else {
-                    debug!("Shutdown WebX Engine for {} on display {}", self.username(), self.display_id());
+    pub fn is_engine_running(&mut self) -> bool {
+        self.engine.is_running()
+    }
+
+    pub fn replace_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.engine_restarts += 1;
+    }
+
+    pub fn x11_session(&self) -> &X11Session {
+        return &self.x11_session;
+    }
+
+    pub fn set_recording(&mut self, recording: Child) {
+        self.recording = Some(recording);
+    }
+
+    pub fn cgroup(&self) -> Option<&CgroupV2> {
+        return self.cgroup.as_ref();
+    }
+
+    pub fn set_cgroup(&mut self, cgroup: CgroupV2) {
+        self.cgroup = Some(cgroup);
+    }
+
+    pub fn stop(&mut self, engine_stop_timeout: Duration) {
+        // Stop the recording first so that ffmpeg can finalise the video file
+        if let Some(mut recording) = self.recording.take() {
+            match recording.term() {
+                Ok(_) => {
+                    if let Err(error) = recording.wait() {
+                        warn!("Failed to wait for recording of session {} to terminate: {}", self.id(), error);
+                    }
+                },
+                Err(error) => error!("Failed to terminate recording of session {}: {}", self.id(), error),
+            }
+        }
+
+        let ipc_path = self.engine.ipc().to_string();
+        let process_id = self.engine.process_id();
+        let username = self.username().to_string();
+        let display_id = self.display_id().to_string();
+
+        match self.engine.process() {
+            Some(process) => match process.interrupt() {
+                Ok(_) => {
+                    match System::wait_with_timeout(process, engine_stop_timeout) {
+                        Ok(Some(_)) => debug!("Shutdown WebX Engine for {} on display {}", username, display_id),
+                        Ok(None) => {
+                            // Make sure the engine has stopped before its resources are cleaned up
+                            warn!("WebX Engine for {} running on PID {} did not stop within {}s: killing it", username, process_id, engine_stop_timeout.as_secs());
+                            if let Err(error) = process.kill().and_then(|_| process.wait()) {
+                                error!("Failed to kill WebX Engine for {} running on PID {}: {}", username, process_id, error);
+                            }
+                        },
+                        Err(error) => warn!("Failed to wait for WebX Engine for {} running on PID {} to terminate: {}", username, process_id, error),
+                    }
 
                     // Delete the IPC socket file
                     let _ = fs::remove_file(ipc_path);
-                }
+                },
+                Err(error) => error!("Failed to interrupt WebX Engine for {} running on PID {}: {}", username, process_id, error),
             },
-            Err(error) => error!("Failed to interrupt WebX Engine for {} running on PID {}: {}", self.username(), process_id, error),
+            None => {
+                // Recovered engines are not our children so they cannot be waited for: poll the process instead
+                match kill(Pid::from_raw(process_id as i32), Signal::SIGINT) {
+                    Ok(_) => {
+                        let start_time = Instant::now();
+                        while self.engine.is_running() && start_time.elapsed() < engine_stop_timeout {
+                            thread::sleep(RECOVERED_ENGINE_POLL_INTERVAL);
+                        }
+                        if self.engine.is_running() {
+                            warn!("Recovered WebX Engine for {} running on PID {} did not stop within {}s: killing it", username, process_id, engine_stop_timeout.as_secs());
+                            if let Err(error) = kill(Pid::from_raw(process_id as i32), Signal::SIGKILL) {
+                                error!("Failed to kill recovered WebX Engine for {} running on PID {}: {}", username, process_id, error);
+                            }
+                        }
+
+                        debug!("Interrupted recovered WebX Engine for {} on display {}", username, display_id);
+                        let _ = fs::remove_file(ipc_path);
+                    },
+                    Err(error) => error!("Failed to interrupt recovered WebX Engine for {} running on PID {}: {}", username, process_id, error),
+                }
+            }
         }
 
+        if let Some(cgroup) = self.cgroup.take() {
+            cgroup.remove();
+        }
     }
 
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
-use crate::common::{Session, X11Session};
+use crate::common::{Session, X11Session, Result, RouterError};
+
+use std::time::Duration;
 
 pub struct SessionContainer {
     sessions: Vec<Session>,
@@ -16,8 +18,16 @@ impl SessionContainer {
         self.sessions.push(session);
     }
 
-    pub fn get_session_by_username(&self, username: &str) -> Option<&Session> {
-        self.sessions.iter().find(|session| session.username() == username)
+    pub fn get_sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    pub fn get_sessions_for_user(&self, username: &str) -> Vec<&Session> {
+        self.sessions.iter().filter(|session| session.username() == username).collect()
+    }
+
+    pub fn get_session_count_for_user(&self, username: &str) -> usize {
+        self.get_sessions_for_user(username).len()
     }
 
     pub fn get_session_by_session_id(&self, session_id: &str) -> Option<&Session> {
@@ -28,21 +38,25 @@ impl SessionContainer {
         self.sessions.iter_mut().find(|session| session.id() == session_id)
     }
 
+    pub fn get_session_by_display(&self, display_id: &str) -> Option<&Session> {
+        self.sessions.iter().find(|session| session.display_id() == display_id)
+    }
+
     pub fn get_session_by_x11session(&self, x11_session: &X11Session) -> Option<&Session> {
         self.sessions.iter().find(|session| session.username() == x11_session.username() && session.id() == x11_session.session_id() && session.display_id() == x11_session.display_id())
     }
 
-    pub fn stop_sessions(&mut self) {
+    pub fn stop_sessions(&mut self, engine_stop_timeout: Duration) {
         for session in self.sessions.iter_mut() {
-            session.stop();
+            session.stop(engine_stop_timeout);
         }
 
         self.sessions.clear();
     }
 
-    pub fn remove_session_for_user(&mut self, username: &str) {
+    pub fn remove_session_for_user(&mut self, username: &str, engine_stop_timeout: Duration) {
         if let Some(session) = self.sessions.iter_mut().find(|session| session.username() == username) {
-            session.stop();
+            session.stop(engine_stop_timeout);
         }
 
         if let Some(index) = self.sessions.iter().position(|a_session| a_session.username() == username) {
@@ -50,21 +64,130 @@ impl SessionContainer {
         }
     }
 
-    pub fn remove_session_with_id(&mut self, session_id: &str) {
-        if let Some(session) = self.sessions.iter_mut().find(|session| session.id() == session_id) {
-            session.stop();
+    pub fn remove_session_with_id(&mut self, session_id: &str, engine_stop_timeout: Duration) -> Result<()> {
+        match self.sessions.iter().position(|session| session.id() == session_id) {
+            Some(index) => {
+                let mut session = self.sessions.remove(index);
+                session.stop(engine_stop_timeout);
+                Ok(())
+            },
+            None => Err(RouterError::SessionError(format!("Session not found with id {}", session_id)))
         }
+    }
 
-        if let Some(index) = self.sessions.iter().position(|a_session| a_session.id() == session_id) {
-            self.sessions.remove(index);        
+    pub fn remove_sessions_for_uid(&mut self, uid: u32, engine_stop_timeout: Duration) -> Result<Vec<String>> {
+        let session_ids: Vec<String> = self.sessions
+            .iter()
+            .filter(|session| session.uid() == uid)
+            .map(|session| session.id().to_string())
+            .collect();
+
+        if session_ids.is_empty() {
+            return Err(RouterError::SessionError(format!("No sessions found for uid {}", uid)));
         }
+
+        for session_id in session_ids.iter() {
+            self.remove_session_with_id(session_id, engine_stop_timeout)?;
+        }
+
+        Ok(session_ids)
+    }
+
+    pub fn get_session_uids(&self) -> Vec<u32> {
+        let mut uids: Vec<u32> = self.sessions.iter().map(|session| session.uid()).collect();
+        uids.sort_unstable();
+        uids.dedup();
+        uids
+    }
+
+    pub fn get_crashed_session_ids(&mut self) -> Vec<String> {
+        self.sessions
+            .iter_mut()
+            .filter_map(|session| if session.is_engine_running() { None } else { Some(session.id().to_string()) })
+            .collect()
+    }
+
+    pub fn get_expired_session_ids(&self, max_session_duration_s: u64) -> Vec<(String, String)> {
+        self.sessions
+            .iter()
+            .filter(|session| session.duration_s() >= max_session_duration_s)
+            .map(|session| (session.id().to_string(), session.username().to_string()))
+            .collect()
+    }
+
+    pub fn get_active_sessions(&self, within_s: u64) -> Vec<&Session> {
+        self.sessions
+            .iter()
+            .filter(|session| session.is_active(within_s))
+            .collect()
     }
 
     pub fn get_inactive_session_ids(&self, session_inactivity_s: u64) -> Vec<(String, String)> {
         self.sessions
             .iter()
-            .filter(|session| !session.is_active(session_inactivity_s))
+            // Suspended sessions are not considered inactive
+            .filter(|session| !session.is_suspended() && !session.is_active(session_inactivity_s))
             .map(|session| (session.id().to_string(), session.username().to_string()))
             .collect()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn create_session(session_id: &str, username: &str, uid: u32) -> Session {
+        Session::for_test(session_id, username, uid, ":10")
+    }
+
+    #[test]
+    fn remove_session_with_id_removes_existing_session() {
+        let mut container = SessionContainer::new();
+        container.add_session(create_session("a", "alice", 1000));
+        container.add_session(create_session("b", "bob", 1001));
+
+        assert!(container.remove_session_with_id("a", STOP_TIMEOUT).is_ok());
+        assert!(container.get_session_by_session_id("a").is_none());
+        assert_eq!(container.get_sessions().len(), 1);
+
+        container.stop_sessions(STOP_TIMEOUT);
+    }
+
+    #[test]
+    fn remove_session_with_id_fails_for_unknown_session() {
+        let mut container = SessionContainer::new();
+        container.add_session(create_session("a", "alice", 1000));
+
+        assert!(matches!(container.remove_session_with_id("unknown", STOP_TIMEOUT), Err(RouterError::SessionError(_))));
+        assert_eq!(container.get_sessions().len(), 1);
+
+        container.stop_sessions(STOP_TIMEOUT);
+    }
+
+    #[test]
+    fn remove_sessions_for_uid_removes_all_sessions_of_the_user() {
+        let mut container = SessionContainer::new();
+        container.add_session(create_session("a", "alice", 1000));
+        container.add_session(create_session("b", "alice", 1000));
+        container.add_session(create_session("c", "bob", 1001));
+
+        let session_ids = container.remove_sessions_for_uid(1000, STOP_TIMEOUT).unwrap();
+        assert_eq!(session_ids, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(container.get_session_uids(), vec![1001]);
+
+        container.stop_sessions(STOP_TIMEOUT);
+    }
+
+    #[test]
+    fn remove_sessions_for_uid_fails_when_user_has_no_sessions() {
+        let mut container = SessionContainer::new();
+        container.add_session(create_session("a", "alice", 1000));
+
+        assert!(matches!(container.remove_sessions_for_uid(1001, STOP_TIMEOUT), Err(RouterError::SessionError(_))));
+        assert_eq!(container.get_sessions().len(), 1);
+
+        container.stop_sessions(STOP_TIMEOUT);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::common::ScreenResolution;
+
+pub struct SessionRequest {
+    username: String,
+    password: String,
+    resolution: ScreenResolution,
+    keyboard: String,
+    // Empty when sessions are not isolated by tenant
+    tenant: String,
+}
+
+impl SessionRequest {
+
+    pub fn new(username: String, password: String, resolution: ScreenResolution, keyboard: String, tenant: String) -> Self {
+        Self {
+            username,
+            password,
+            resolution,
+            keyboard,
+            tenant,
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        return &self.username;
+    }
+
+    pub fn password(&self) -> &str {
+        return &self.password;
+    }
+
+    pub fn resolution(&self) -> &ScreenResolution {
+        return &self.resolution;
+    }
+
+    pub fn keyboard(&self) -> &str {
+        return &self.keyboard;
+    }
+
+    pub fn tenant(&self) -> &str {
+        return &self.tenant;
+    }
+}
@@ -1,22 +1,45 @@
-use serde::Deserialize;
+use crate::common::{System, ScreenResolution, RouterError, IpFilter};
+
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PortSettings {
     pub connector: u32,
     pub publisher: u32,
     pub collector: u32,
     pub session: u32,
+    pub metrics: Option<u32>,
+    pub health: Option<u32>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EncryptionSettings {
     pub public: String,
-    pub private: String
+    pub private: String,
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HealthSettings {
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+}
+
+impl HealthSettings {
+    // TLS is only enabled when both the certificate and the key are set
+    pub fn tls_paths(&self) -> Option<(&str, &str)> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+            _ => None
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct IPCSettings {
     pub message_proxy: String,
     pub instruction_proxy: String,
@@ -24,38 +47,427 @@ pub struct IPCSettings {
     pub sesman_connector: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransportSettings {
     pub ports: PortSettings,
     pub ipc: IPCSettings,
     pub encryption: EncryptionSettings,
+    pub trace_sample_rate: Option<f32>,
+    pub bind_address: Option<String>,
+    pub connector_bind_address: Option<String>,
+    pub publisher_bind_address: Option<String>,
+    pub collector_bind_address: Option<String>,
+    pub session_bind_address: Option<String>,
+    #[serde(default)]
+    pub compress_relay: bool,
+    pub compression_threshold_bytes: Option<usize>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    #[serde(default)]
+    pub blocked_ips: Vec<String>,
+    pub admin_socket_path: Option<String>,
+    pub admin_socket_group: Option<String>,
+    pub monitor_socket_path: Option<String>,
+    #[serde(default)]
+    pub health: HealthSettings,
+    pub keepalive: Option<KeepaliveSettings>,
+    #[serde(default)]
+    pub hwm: HwmSettings,
+}
+
+// Omitted idle, interval and count values use the operating system defaults
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeepaliveSettings {
+    pub enabled: bool,
+    pub idle_s: Option<i32>,
+    pub interval_s: Option<i32>,
+    pub count: Option<i32>,
+}
+
+// Maximum number of messages queued by the proxies (frames are dropped once reached)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HwmSettings {
+    pub message_proxy: Option<i32>,
+    pub instruction_proxy: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+static DEFAULT_MESSAGE_PROXY_HWM: i32 = 100;
+static DEFAULT_INSTRUCTION_PROXY_HWM: i32 = 1000;
+
+impl HwmSettings {
+    pub fn message_proxy(&self) -> i32 {
+        self.message_proxy.unwrap_or(DEFAULT_MESSAGE_PROXY_HWM)
+    }
+
+    pub fn instruction_proxy(&self) -> i32 {
+        self.instruction_proxy.unwrap_or(DEFAULT_INSTRUCTION_PROXY_HWM)
+    }
+}
+
+static DEFAULT_COMPRESSION_THRESHOLD_BYTES: usize = 1024;
+static DEFAULT_ADMIN_SOCKET_GROUP: &str = "webx";
+
+impl TransportSettings {
+    pub fn trace_sample_rate(&self) -> f32 {
+        self.trace_sample_rate.unwrap_or(1.0)
+    }
+
+    pub fn compression_threshold_bytes(&self) -> usize {
+        self.compression_threshold_bytes.unwrap_or(DEFAULT_COMPRESSION_THRESHOLD_BYTES)
+    }
+
+    pub fn admin_socket_group(&self) -> &str {
+        self.admin_socket_group.as_deref().unwrap_or(DEFAULT_ADMIN_SOCKET_GROUP)
+    }
+
+    pub fn bind_address(&self) -> &str {
+        self.bind_address.as_deref().unwrap_or("*")
+    }
+
+    // Per-component bind addresses fall back to the global one
+    pub fn connector_bind_address(&self) -> &str {
+        self.connector_bind_address.as_deref().unwrap_or_else(|| self.bind_address())
+    }
+
+    pub fn publisher_bind_address(&self) -> &str {
+        self.publisher_bind_address.as_deref().unwrap_or_else(|| self.bind_address())
+    }
+
+    pub fn collector_bind_address(&self) -> &str {
+        self.collector_bind_address.as_deref().unwrap_or_else(|| self.bind_address())
+    }
+
+    pub fn session_bind_address(&self) -> &str {
+        self.session_bind_address.as_deref().unwrap_or_else(|| self.bind_address())
+    }
+
+    fn is_valid_bind_address(address: &str) -> bool {
+        address == "*" || address.parse::<IpAddr>().is_ok()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EngineSettings {
     pub path: String,
     pub logdir: String,
+    pub log_path_template: Option<String>,
+    #[serde(default)]
+    pub log_dir_per_user: bool,
+    pub stderr_path_template: Option<String>,
+    pub max_response_size_bytes: Option<usize>,
+    pub max_restarts: Option<u32>,
+    pub restart_check_interval_s: Option<u64>,
+    pub log_max_size_bytes: Option<u64>,
+    pub log_max_files: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub hooks: Option<HookSettings>,
+    pub socket_pool_size: Option<usize>,
+    #[serde(default)]
+    pub env_allowlist: Vec<String>,
+    #[serde(default)]
+    pub env_blocklist: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HookSettings {
+    pub pre_create: Option<String>,
+    pub post_create: Option<String>,
+    pub pre_destroy: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub backoff_factor: f64,
+}
+
+impl RetryPolicy {
+    pub fn single_attempt() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay_ms: 0,
+            backoff_factor: 1.0,
+        }
+    }
+
+    pub fn delay(&self, attempt: u32) -> Duration {
+        // Delay before the retry following the given (zero-based) attempt
+        let delay_ms = self.initial_delay_ms as f64 * self.backoff_factor.powi(attempt as i32);
+        Duration::from_millis(delay_ms as u64)
+    }
+}
+
+static DEFAULT_ENGINE_LOG_PATH_TEMPLATE: &str = "{root}/webx-engine.{session_id}.log";
+static DEFAULT_ENGINE_MAX_RESPONSE_SIZE_BYTES: usize = 1024 * 1024;
+static DEFAULT_ENGINE_MAX_RESTARTS: u32 = 3;
+static DEFAULT_ENGINE_RESTART_CHECK_INTERVAL_S: u64 = 10;
+static DEFAULT_ENGINE_LOG_MAX_FILES: u32 = 5;
+static DEFAULT_ENGINE_SOCKET_POOL_SIZE: usize = 1;
+static DEFAULT_ENGINE_RETRY_POLICY: RetryPolicy = RetryPolicy { max_attempts: 3, initial_delay_ms: 200, backoff_factor: 2.0 };
+
+impl EngineSettings {
+    // Patterns ending with * match any variable name starting with the prefix
+    pub fn is_environment_variable_allowed(&self, name: &str) -> bool {
+        let matches = |pattern: &String| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        };
+
+        let is_allowed = self.env_allowlist.is_empty() || self.env_allowlist.iter().any(matches);
+        is_allowed && !self.env_blocklist.iter().any(matches)
+    }
+
+    pub fn max_restarts(&self) -> u32 {
+        self.max_restarts.unwrap_or(DEFAULT_ENGINE_MAX_RESTARTS)
+    }
+
+    pub fn restart_check_interval_s(&self) -> u64 {
+        self.restart_check_interval_s.unwrap_or(DEFAULT_ENGINE_RESTART_CHECK_INTERVAL_S)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.unwrap_or(DEFAULT_ENGINE_RETRY_POLICY)
+    }
+
+    pub fn socket_pool_size(&self) -> usize {
+        self.socket_pool_size.unwrap_or(DEFAULT_ENGINE_SOCKET_POOL_SIZE)
+    }
+
+    pub fn log_max_files(&self) -> u32 {
+        self.log_max_files.unwrap_or(DEFAULT_ENGINE_LOG_MAX_FILES)
+    }
+
+    pub fn max_response_size_bytes(&self) -> usize {
+        self.max_response_size_bytes.unwrap_or(DEFAULT_ENGINE_MAX_RESPONSE_SIZE_BYTES)
+    }
+
+    pub fn log_path_template(&self) -> &str {
+        match &self.log_path_template {
+            Some(template) => template,
+            None => DEFAULT_ENGINE_LOG_PATH_TEMPLATE,
+        }
+    }
+
+    pub fn resolve_log_path(&self, template: &str, session_id: &str, username: &str) -> String {
+        // Replace the template variables
+        let path = self.replace_root(template)
+            .replace("{session_id}", session_id)
+            .replace("{username}", username)
+            .replace("{date}", &System::current_date());
+
+        // Place the log file in a sub-directory for each user if required
+        if self.log_dir_per_user {
+            let path = Path::new(&path);
+            if let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) {
+                return parent.join(username).join(file_name).to_string_lossy().to_string();
+            }
+        }
+
+        path
+    }
+
+    fn replace_root(&self, template: &str) -> String {
+        // {logdir} is kept as an alias of {root} for existing templates
+        template
+            .replace("{root}", &self.logdir)
+            .replace("{logdir}", &self.logdir)
+    }
+
+    fn template_root_dir(&self, template: &str) -> PathBuf {
+        // Get the directory up to the first templated variable
+        let template = self.replace_root(template);
+        let fixed_part = match template.find('{') {
+            Some(index) => &template[..index],
+            None => &template,
+        };
+
+        if fixed_part.ends_with('/') {
+            PathBuf::from(fixed_part)
+
+        } else {
+            match Path::new(fixed_part).parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => PathBuf::from("."),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SesManSettings {
     pub enabled: bool,
     // pub url: String,
     pub fallback_display_id: String,
     pub auto_logout_s: u64,
+    pub default_resolution: Option<String>,
+    pub authentication_timeout_s: Option<u64>,
+    pub required_groups: Option<Vec<String>>,
+    pub excluded_groups: Option<Vec<String>>,
+    #[serde(default)]
+    pub deterministic_session_ids: bool,
+    pub test_credentials_path: Option<String>,
+    pub session_store_path: Option<String>,
+    pub rate_limit: Option<RateLimitSettings>,
+    pub max_sessions_per_user: Option<u32>,
+    pub client_heartbeat_interval_s: Option<u64>,
+    #[serde(default)]
+    pub max_session_duration_s: u64,
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub who_activity_window_s: Option<u64>,
+    #[serde(default)]
+    pub shutdown_grace_period_s: u64,
+    pub max_connections_per_session: Option<u32>,
+    pub recording: Option<RecordingSettings>,
+    pub resource_limits: Option<ResourceLimitSettings>,
+    pub engine_stop_timeout_s: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitSettings {
+    pub max_creates_per_minute: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResourceLimitSettings {
+    pub max_cpu_percent: Option<u32>,
+    pub max_memory_mb: Option<u64>,
+    pub max_pids: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordingSettings {
+    pub enabled: bool,
+    pub path: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+static DEFAULT_SESMAN_AUTHENTICATION_TIMEOUT_S: u64 = 30;
+static DEFAULT_SESMAN_TEST_CREDENTIALS_PATH: &str = "/etc/webx/webx-router-test-credentials";
+static DEFAULT_SESMAN_WHO_ACTIVITY_WINDOW_S: u64 = 60;
+static DEFAULT_SESMAN_ENGINE_STOP_TIMEOUT_S: u64 = 5;
+static DEFAULT_SESMAN_MIN_WIDTH: u32 = 320;
+static DEFAULT_SESMAN_MIN_HEIGHT: u32 = 240;
+static DEFAULT_SESMAN_MAX_WIDTH: u32 = 7680;
+static DEFAULT_SESMAN_MAX_HEIGHT: u32 = 4320;
+
+impl SesManSettings {
+    pub fn max_sessions_per_user(&self) -> u32 {
+        self.max_sessions_per_user.unwrap_or(1)
+    }
+
+    pub fn test_credentials_path(&self) -> &str {
+        self.test_credentials_path.as_deref().unwrap_or(DEFAULT_SESMAN_TEST_CREDENTIALS_PATH)
+    }
+
+    pub fn authentication_timeout_s(&self) -> u64 {
+        self.authentication_timeout_s.unwrap_or(DEFAULT_SESMAN_AUTHENTICATION_TIMEOUT_S)
+    }
+
+    pub fn who_activity_window_s(&self) -> u64 {
+        self.who_activity_window_s.unwrap_or(DEFAULT_SESMAN_WHO_ACTIVITY_WINDOW_S)
+    }
+
+    pub fn engine_stop_timeout(&self) -> Duration {
+        Duration::from_secs(self.engine_stop_timeout_s.unwrap_or(DEFAULT_SESMAN_ENGINE_STOP_TIMEOUT_S))
+    }
+
+    pub fn recording_path(&self) -> Option<&str> {
+        self.recording.as_ref().filter(|recording| recording.enabled).map(|recording| recording.path.as_str())
+    }
+
+    pub fn max_connections_per_session(&self) -> u32 {
+        self.max_connections_per_session.unwrap_or(1)
+    }
+
+    pub fn min_width(&self) -> u32 {
+        self.min_width.unwrap_or(DEFAULT_SESMAN_MIN_WIDTH)
+    }
+
+    pub fn min_height(&self) -> u32 {
+        self.min_height.unwrap_or(DEFAULT_SESMAN_MIN_HEIGHT)
+    }
+
+    // Resolutions are checked against the limits when sessions are created or resized
+    pub fn validate_resolution(&self, resolution: &ScreenResolution) -> Result<(), RouterError> {
+        resolution.validate(self.min_width(), self.min_height(), self.max_width(), self.max_height())
+    }
+
+    pub fn max_width(&self) -> u32 {
+        self.max_width.unwrap_or(DEFAULT_SESMAN_MAX_WIDTH)
+    }
+
+    pub fn max_height(&self) -> u32 {
+        self.max_height.unwrap_or(DEFAULT_SESMAN_MAX_HEIGHT)
+    }
+
+    pub fn default_resolution(&self) -> Option<Result<ScreenResolution, RouterError>> {
+        // The default resolution can either be WIDTHxHEIGHT or a preset name
+        self.default_resolution.as_ref().map(|resolution| {
+            resolution.parse::<ScreenResolution>().or_else(|_| ScreenResolution::from_preset_name(resolution))
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "LoggingSettingsValue")]
+pub struct LoggingSettings {
+    pub level: String,
+    pub format: Option<String>,
+    pub audit: Option<AuditSettings>,
+}
+
+// Older configurations (and WEBX_ROUTER_LOGGING overrides) only give the level as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoggingSettingsValue {
+    Level(String),
+    Settings {
+        level: String,
+        format: Option<String>,
+        audit: Option<AuditSettings>,
+    },
+}
+
+impl From<LoggingSettingsValue> for LoggingSettings {
+    fn from(value: LoggingSettingsValue) -> Self {
+        match value {
+            LoggingSettingsValue::Level(level) => LoggingSettings { level, format: None, audit: None },
+            LoggingSettingsValue::Settings { level, format, audit } => LoggingSettings { level, format, audit },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditSettings {
+    pub path: String,
+}
+
+impl LoggingSettings {
+    pub fn format(&self) -> &str {
+        self.format.as_deref().unwrap_or("text")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
-    pub logging: String,
+    pub logging: LoggingSettings,
     pub transport: TransportSettings,
     pub sesman: SesManSettings,
-    pub engine: EngineSettings
+    pub engine: EngineSettings,
+    pub pid_file: Option<String>,
 }
 
 static DEFAULT_CONFIG_PATHS: [&str; 2] = ["/etc/webx/webx-router-config.yml", "./config.yml"];
+static DEFAULT_PID_FILE: &str = "/run/webx/webx-router.pid";
 
 impl Settings {
+    pub fn pid_file(&self) -> &str {
+        self.pid_file.as_deref().unwrap_or(DEFAULT_PID_FILE)
+    }
+
     pub fn new(config_path: &str) -> Result<Self, config::ConfigError> {
 
         let config_path = Settings::get_config_path(config_path);
@@ -68,6 +480,19 @@ impl Settings {
         settings_raw.try_into()
     }
 
+    pub fn reload(&mut self, config_path: &str) -> Result<(), config::ConfigError> {
+        let settings = Settings::new(config_path)?;
+        if !settings.verify() {
+            return Err(config::ConfigError::Message("Reloaded settings are not valid".to_string()));
+        }
+
+        // Sockets are already bound and logging initialised: only the session and engine settings can be changed
+        self.sesman = settings.sesman;
+        self.engine = settings.engine;
+
+        Ok(())
+    }
+
     pub fn verify(&self) -> bool {
         // Check that settings are valid for running a router
 
@@ -77,12 +502,82 @@ impl Settings {
             return false;
         }
 
+        // Verify logging format
+        if !["text", "json"].contains(&self.logging.format()) {
+            error!("Logging format must be \"text\" or \"json\", got \"{}\"", self.logging.format());
+            return false;
+        }
+
+        // Verify trace sample rate
+        let trace_sample_rate = self.transport.trace_sample_rate();
+        if !(0.0..=1.0).contains(&trace_sample_rate) {
+            error!("Transport trace sample rate must be between 0.0 and 1.0, got {}", trace_sample_rate);
+            return false;
+        }
+
+        // Verify bind addresses
+        let bind_addresses = [
+            self.transport.bind_address(),
+            self.transport.connector_bind_address(),
+            self.transport.publisher_bind_address(),
+            self.transport.collector_bind_address(),
+            self.transport.session_bind_address(),
+        ];
+        if let Some(bind_address) = bind_addresses.iter().find(|address| !TransportSettings::is_valid_bind_address(address)) {
+            error!("Transport bind address \"{}\" is not a valid IPv4 or IPv6 address or \"*\"", bind_address);
+            return false;
+        }
+
+        // Verify IP filters
+        if let Err(error) = IpFilter::new(&self.transport.allowed_ips, &self.transport.blocked_ips) {
+            error!("Transport IP filter is invalid: {}", error);
+            return false;
+        }
+
+        // Verify health TLS files
+        let health = &self.transport.health;
+        if health.tls_cert_path.is_some() != health.tls_key_path.is_some() {
+            error!("Health TLS requires both tls_cert_path and tls_key_path");
+            return false;
+        }
+
+        // Verify engine retry policy
+        let retry_policy = self.engine.retry_policy();
+        if retry_policy.max_attempts == 0 || retry_policy.backoff_factor < 1.0 {
+            error!("Engine retry policy requires at least 1 attempt and a backoff factor of at least 1.0");
+            return false;
+        }
+
+        // Verify default resolution
+        if let Some(Err(error)) = self.sesman.default_resolution() {
+            error!("Default resolution is invalid: {}", error);
+            return false;
+        }
+
         // Verify engine log dir
         if let Err(error) = fs::create_dir_all(&self.engine.logdir) {
             error!("Cannot create engine log directory at {}: {}", self.engine.logdir, error);
             return false;
         }
 
+        // Verify engine log paths are writable
+        let mut log_path_templates = vec![self.engine.log_path_template()];
+        if let Some(stderr_path_template) = &self.engine.stderr_path_template {
+            log_path_templates.push(stderr_path_template);
+        }
+        for template in log_path_templates {
+            let log_dir = self.engine.template_root_dir(template);
+            if let Err(error) = fs::create_dir_all(&log_dir) {
+                error!("Cannot create engine log directory at {}: {}", log_dir.display(), error);
+                return false;
+            }
+
+            if !System::is_dir_writable(&log_dir) {
+                error!("Engine log directory {} is not writable", log_dir.display());
+                return false;
+            }
+        }
+
         true
     }
 
@@ -97,3 +592,44 @@ impl Settings {
         return config_path;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logging_level_string_is_accepted() {
+        let logging: LoggingSettings = serde_yaml::from_str("info").unwrap();
+        assert_eq!(logging.level, "info");
+        assert_eq!(logging.format(), "text");
+        assert!(logging.audit.is_none());
+    }
+
+    #[test]
+    fn logging_settings_are_accepted() {
+        let logging: LoggingSettings = serde_yaml::from_str("level: debug\nformat: json\naudit:\n  path: /var/log/webx/audit.log").unwrap();
+        assert_eq!(logging.level, "debug");
+        assert_eq!(logging.format(), "json");
+        assert_eq!(logging.audit.map(|audit| audit.path), Some("/var/log/webx/audit.log".to_string()));
+    }
+
+    fn create_engine_settings() -> EngineSettings {
+        serde_yaml::from_str("path: /usr/bin/webx-engine\nlogdir: /var/log/webx/engine").unwrap()
+    }
+
+    #[test]
+    fn default_log_path_is_in_root_dir() {
+        let engine_settings = create_engine_settings();
+        let log_path = engine_settings.resolve_log_path(engine_settings.log_path_template(), "abc", "alice");
+        assert_eq!(log_path, "/var/log/webx/engine/webx-engine.abc.log");
+    }
+
+    #[test]
+    fn logdir_is_an_alias_of_root() {
+        let mut engine_settings = create_engine_settings();
+        engine_settings.log_path_template = Some("{logdir}/{username}.{session_id}.log".to_string());
+        let log_path = engine_settings.resolve_log_path(engine_settings.log_path_template(), "abc", "alice");
+        assert_eq!(log_path, "/var/log/webx/engine/alice.abc.log");
+        assert_eq!(engine_settings.template_root_dir("{root}/{username}/engine.log"), PathBuf::from("/var/log/webx/engine/"));
+    }
+}
@@ -1,9 +1,15 @@
 use crate::common::{Result, RouterError};
-use std::process::{Command};
+use std::io::ErrorKind;
+use std::process::{self, Child, Command, ExitStatus};
 use std::fs;
-use std::fs::Permissions;
+use std::fs::{File, OpenOptions, Permissions};
 use std::os::unix::fs::PermissionsExt;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use nix::unistd::{self, Group, User, Uid};
+
+static PROCESS_WAIT_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct System {
 }
@@ -26,6 +32,47 @@ impl System {
             }
     }
 
+    pub fn get_current_uid() -> u32 {
+        Uid::current().as_raw()
+    }
+
+    pub fn user_exists(uid: u32) -> Result<bool> {
+        match User::from_uid(Uid::from_raw(uid)) {
+            Ok(user) => Ok(user.is_some()),
+            Err(error) => Err(RouterError::SystemError(format!("Failed to get user with uid {}: {}", uid, error))),
+        }
+    }
+
+    pub fn spawn_with_context(command: &mut Command, binary_path: &str) -> Result<Child> {
+        match command.spawn() {
+            Ok(child) => Ok(child),
+            Err(error) if error.kind() == ErrorKind::NotFound => Err(RouterError::SessionError(format!("Binary not found at '{}': {}", binary_path, error))),
+            Err(error) => Err(RouterError::SessionError(format!("Failed to spawn '{}': {}", binary_path, error))),
+        }
+    }
+
+    pub fn is_process_running_binary(process_id: u32, binary_path: &str) -> bool {
+        // Protects against signalling an unrelated process that has reused the PID
+        match (fs::read_link(format!("/proc/{}/exe", process_id)), fs::canonicalize(binary_path)) {
+            (Ok(process_binary_path), Ok(binary_path)) => process_binary_path == binary_path,
+            _ => false,
+        }
+    }
+
+    // Returns None if the process is still running after the timeout
+    pub fn wait_with_timeout(process: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+        let start_time = Instant::now();
+        loop {
+            if let Some(status) = process.try_wait()? {
+                return Ok(Some(status));
+            }
+            if start_time.elapsed() >= timeout {
+                return Ok(None);
+            }
+            thread::sleep(PROCESS_WAIT_INTERVAL);
+        }
+    }
+
     pub fn chmod(path: &str, mode: u32) -> Result<()> {
         let mode = Permissions::from_mode(mode);
         if fs::set_permissions(path, mode).is_err() {
@@ -36,6 +83,61 @@ impl System {
         Ok(())
     }
 
+    pub fn chgrp(path: &str, group_name: &str) -> Result<()> {
+        let group = match Group::from_name(group_name) {
+            Ok(Some(group)) => group,
+            Ok(None) => return Err(RouterError::SystemError(format!("Group \"{}\" does not exist", group_name))),
+            Err(error) => return Err(RouterError::SystemError(format!("Failed to get group \"{}\": {}", group_name, error))),
+        };
+
+        if let Err(error) = unistd::chown(path, None, Some(group.gid)) {
+            return Err(RouterError::SystemError(format!("Could not change group of {}: {}", path, error)));
+        }
+
+        debug!("Changed group of {} to {}", path, group_name);
+        Ok(())
+    }
+
+    pub fn create_dir(path: &Path, mode: u32) -> Result<()> {
+        if !path.exists() {
+            fs::create_dir_all(path)?;
+            System::chmod(&path.to_string_lossy(), mode)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn rotate_log_file(path: &str, max_files: u32) -> Result<()> {
+        // Shift the older rotations, dropping the oldest one
+        for index in (1..max_files).rev() {
+            let rotated_path = format!("{}.{}", path, index);
+            if Path::new(&rotated_path).exists() {
+                fs::rename(&rotated_path, format!("{}.{}", path, index + 1))?;
+            }
+        }
+
+        // Copy and truncate: the process writing to the file keeps its file descriptor
+        if max_files > 0 {
+            fs::copy(path, format!("{}.1", path))?;
+        }
+        OpenOptions::new().write(true).open(path)?.set_len(0)?;
+
+        debug!("Rotated log file {}", path);
+        Ok(())
+    }
+
+    pub fn is_dir_writable(path: &Path) -> bool {
+        // Try to create a file in the directory
+        let test_file_path = path.join(format!(".webx-router-{}", process::id()));
+        match File::create(&test_file_path) {
+            Ok(_) => {
+                let _ = fs::remove_file(&test_file_path);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
     pub fn current_time_s() -> u64 {
         if let Ok(current_time) = SystemTime::now().duration_since(UNIX_EPOCH) {
             current_time.as_secs()
@@ -44,4 +146,22 @@ impl System {
             0
         }
     }
-}
\ No newline at end of file
+
+    pub fn current_date() -> String {
+        // Current UTC date formatted as YYYY-MM-DD
+        let days = (System::current_time_s() / 86400) as i64;
+
+        // Convert days since the epoch to a civil date
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
@@ -2,16 +2,18 @@
 pub struct X11Session {
     session_id: String,
     username: String,
+    uid: u32,
     display_id: String,
     xauthority_file_path: String,
 }
 
 impl X11Session {
 
-    pub fn new(session_id: String, username: String, display_id: String, xauthority_file_path: String) -> Self {
+    pub fn new(session_id: String, username: String, uid: u32, display_id: String, xauthority_file_path: String) -> Self {
         Self {
             session_id,
             username,
+            uid,
             display_id,
             xauthority_file_path
         }
@@ -25,6 +27,10 @@ impl X11Session {
         return &self.username;
     }
 
+    pub fn uid(&self) -> u32 {
+        return self.uid;
+    }
+
     pub fn display_id(&self) -> &str {
         return &self.display_id;
     }
@@ -0,0 +1,10 @@
+// The router modules are also built as a library so that the integration tests can drive them
+#![allow(clippy::new_without_default)]
+
+#[macro_use]
+extern crate log;
+
+pub mod app;
+pub mod common;
+pub mod service;
+mod router;
@@ -2,35 +2,77 @@
 extern crate log;
 extern crate dotenv;
 
-use crate::app::Application;
-use crate::common::Settings;
+use webx_router::app::Application;
+use webx_router::common::{Settings, Logging, ScreenResolution};
 
 use structopt::StructOpt;
-use env_logger::Env;
 use dotenv::dotenv;
 use std::process;
 
-mod app;
-mod common;
-mod service;
-mod router;
-
 #[derive(StructOpt, Debug)]
 #[structopt(name = "webx-router")]
 struct Opt {
     /// Config path
     #[structopt(short, long, default_value = "")]
     config: String,
+
+    /// Run a self-test of session creation with the test account and exit
+    #[structopt(long)]
+    self_test: bool,
+
+    /// Read the self-test credentials from the WEBX_USERNAME and WEBX_PASSWORD environment variables
+    #[structopt(long)]
+    credentials_from_env: bool,
+
+    /// Read the self-test credentials from this file instead of sesman.test_credentials_path
+    #[structopt(long)]
+    credentials_file: Option<String>,
+
+    /// Measure the creation time of this number of sessions with the test account and exit
+    #[structopt(long)]
+    benchmark: Option<u32>,
+
+    /// Screen resolution of the benchmark sessions
+    #[structopt(long, default_value = "1024x768")]
+    benchmark_resolution: ScreenResolution,
+
+    /// Verify the configuration, print a summary of the settings and exit
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Verify the configuration, print the resolved settings as YAML and exit
+    #[structopt(long)]
+    config_check: bool,
+
+    /// Print the resolved settings as YAML without verifying them and exit
+    #[structopt(long)]
+    dump_config: bool,
 }
 
 fn main() {
     dotenv().ok();
     let opt = Opt::from_args();
 
-    let mut settings = Settings::new(&opt.config).expect("Loaded settings");
+    let settings = match Settings::new(&opt.config) {
+        Ok(settings) => settings,
+        Err(error) => {
+            eprintln!("Failed to load settings: {}", error);
+            process::exit(1);
+        }
+    };
 
-    let env = Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, &settings.logging);
-    env_logger::init_from_env(env);
+    // Dumping the configuration must work before the deployment is complete
+    if opt.dump_config {
+        match Application::new().dump_config(&settings) {
+            Ok(_) => process::exit(0),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    Logging::init(&settings.logging);
 
     // Verify settings
     if !settings.verify() {
@@ -38,7 +80,47 @@ fn main() {
         process::exit(1);
     }
 
-    if let Err(error) = Application::new().run(&mut settings) {
+    if opt.config_check {
+        match Application::new().dump_config(&settings) {
+            Ok(_) => process::exit(0),
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    if opt.dry_run {
+        match Application::new().dry_run(&settings) {
+            Ok(_) => process::exit(0),
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    if opt.self_test {
+        match Application::new().self_test(&settings, opt.credentials_file.as_deref(), opt.credentials_from_env) {
+            Ok(_) => process::exit(0),
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(session_count) = opt.benchmark {
+        match Application::new().benchmark(&settings, session_count, opt.benchmark_resolution, opt.credentials_file.as_deref(), opt.credentials_from_env) {
+            Ok(_) => process::exit(0),
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Err(error) = Application::new().run(settings, &opt.config) {
         error!("{}", error);
         process::exit(1);
     }
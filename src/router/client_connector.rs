@@ -1,4 +1,5 @@
 use crate::common::*;
+use crate::router::Transport;
 
 pub struct ClientConnector {
     context: zmq::Context,
@@ -14,14 +15,17 @@ impl ClientConnector {
         }
     }
 
-    pub fn run(&mut self, settings: &Settings) -> Result<()> {
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])
+    }
+
+    pub fn run(&mut self, settings: &Settings, event_bus_sub_socket: zmq::Socket) -> Result<()> {
         let transport = &settings.transport;
 
         // Create REP socket
-        let rep_socket = self.create_rep_socket(transport.ports.connector)?;
+        let rep_socket = self.create_rep_socket(transport.connector_bind_address(), transport.ports.connector, transport.keepalive.as_ref())?;
 
-        // Create event bus SUB
-        let event_bus_sub_socket = EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])?;
+        let ip_filter = IpFilter::new(&transport.allowed_ips, &transport.blocked_ips)?;
 
         let mut items = [
             event_bus_sub_socket.as_poll_item(zmq::POLLIN),
@@ -39,7 +43,7 @@ impl ClientConnector {
 
                 // Check for REQ-REP message (if running)
                 if items[1].is_readable() && self.is_running {
-                    self.handle_request(&rep_socket, transport);
+                    self.handle_request(&rep_socket, transport, &ip_filter);
                 }
             }
         }
@@ -49,14 +53,16 @@ impl ClientConnector {
         Ok(())
     }
 
-    fn create_rep_socket(&self, port: u32) -> Result<zmq::Socket> {
+    fn create_rep_socket(&self, bind_address: &str, port: u32, keepalive: Option<&KeepaliveSettings>) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::REP)?;
         socket.set_linger(0)?;
+        Transport::apply_keepalive(&socket, keepalive)?;
 
-        let address = format!("tcp://*:{}", port);
+        socket.set_ipv6(Transport::is_ipv6_address(bind_address))?;
+        let address = Transport::tcp_address(bind_address, port);
         match socket.bind(address.as_str()) {
             Ok(_) => debug!("Client Connector bound to {}", address),
-            Err(error) => return Err(RouterError::TransportError(format!("Failed to bind REP socket to {}: {}", address, error)))
+            Err(error) => return Err(Transport::bind_error(&format!("connector port {}", port), error))
         }
 
         Ok(socket)
@@ -79,16 +85,23 @@ impl ClientConnector {
         }
     }
     
-    fn handle_request(&self, rep_socket: &zmq::Socket, transport: &TransportSettings) {
+    fn handle_request(&self, rep_socket: &zmq::Socket, transport: &TransportSettings, ip_filter: &IpFilter) {
         let mut msg = zmq::Message::new();
 
         if let Err(error) = rep_socket.recv(&mut msg, 0) {
             error!("Failed to received message on relay req-rep: {}", error);
 
         } else {
+            let peer_address = msg.gets("Peer-Address").unwrap_or_default().to_string();
             let message_text = msg.as_str().unwrap();
 
-            if message_text == "comm" {
+            if !ip_filter.is_allowed(&peer_address) {
+                warn!("Refusing connector request from blocked address \"{}\"", peer_address);
+                if let Err(error) = rep_socket.send(zmq::Message::new(), 0) {
+                    error!("Failed to send empty message: {}", error);
+                }
+
+            } else if message_text == "comm" {
                 // Comm message
                 if let Err(error) = rep_socket.send(format!("{},{},{},{}", 
                     transport.ports.publisher, 
@@ -0,0 +1,78 @@
+use crate::common::*;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+static CURVE_KEY_LENGTH: usize = 32;
+
+pub struct CurveKeyStore {
+    path: String,
+}
+
+impl CurveKeyStore {
+
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+
+    // Returns the Z85 encoded public and secret keys
+    pub fn generate() -> Result<(String, String)> {
+        let key_pair = zmq::CurveKeyPair::new()?;
+        let public_key = zmq::z85_encode(&key_pair.public_key).map_err(|error| RouterError::TransportError(format!("Failed to encode public key: {}", error)))?;
+        let secret_key = zmq::z85_encode(&key_pair.secret_key).map_err(|error| RouterError::TransportError(format!("Failed to encode secret key: {}", error)))?;
+
+        Ok((public_key, secret_key))
+    }
+
+    pub fn load_or_generate(&self) -> Result<(String, String)> {
+        if Path::new(&self.path).exists() {
+            debug!("Loading encryption keys from {}", self.path);
+            return self.load();
+        }
+
+        info!("Generating new encryption keys in {}", self.path);
+        let (public_key, secret_key) = CurveKeyStore::generate()?;
+        self.save(&public_key, &secret_key)?;
+
+        Ok((public_key, secret_key))
+    }
+
+    fn load(&self) -> Result<(String, String)> {
+        // File contains the public and secret keys on separate lines
+        let content = fs::read_to_string(&self.path)?;
+        let mut lines = content.lines().map(|line| line.trim());
+        let (public_key, secret_key) = match (lines.next(), lines.next()) {
+            (Some(public_key), Some(secret_key)) => (public_key.to_string(), secret_key.to_string()),
+            _ => return Err(RouterError::TransportError(format!("Invalid key file {}: expected public and secret keys on separate lines", self.path)))
+        };
+
+        for key in [&public_key, &secret_key] {
+            if zmq::z85_decode(key)?.len() != CURVE_KEY_LENGTH {
+                return Err(RouterError::TransportError(format!("Invalid key file {}: keys must be {} bytes", self.path, CURVE_KEY_LENGTH)));
+            }
+        }
+
+        Ok((public_key, secret_key))
+    }
+
+    fn save(&self, public_key: &str, secret_key: &str) -> Result<()> {
+        if let Some(dir) = Path::new(&self.path).parent() {
+            System::create_dir(dir, 0o700)?;
+        }
+
+        // The secret key must only be readable by the router user
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n{}\n", public_key, secret_key).as_bytes())?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
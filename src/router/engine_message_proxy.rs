@@ -1,37 +1,47 @@
 use crate::common::*;
-use std::process;
+use crate::router::Transport;
+
+use std::sync::Arc;
 
 pub struct EngineMessageProxy {
     context: zmq::Context,
+    metrics: Arc<Metrics>,
     is_running: bool,
 }
 
 impl EngineMessageProxy {
 
-    pub fn new(context: zmq::Context) -> Self {
+    pub fn new(context: zmq::Context, metrics: Arc<Metrics>) -> Self {
         Self {
             context,
+            metrics,
             is_running: false,
         }
     }
 
-    pub fn run(&mut self, settings: &Settings) -> Result<()> {
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])
+    }
+
+    pub fn run(&mut self, settings: &Settings, event_bus_sub_socket: zmq::Socket) -> Result<()> {
         let transport = &settings.transport;
         
-        let relay_publisher_socket = self.create_relay_publisher_socket(transport.ports.publisher)?;
+        let relay_publisher_socket = self.create_relay_publisher_socket(transport.publisher_bind_address(), transport.ports.publisher, transport.keepalive.as_ref(), transport.hwm.message_proxy())?;
 
-        let engine_subscriber_socket = self.create_engine_subscriber_socket(&transport.ipc.message_proxy)?;
+        let engine_subscriber_socket = self.create_engine_subscriber_socket(&transport.ipc.message_proxy, transport.hwm.message_proxy())?;
 
-        let event_bus_sub_socket = EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])?;
+        // Log relay connections and disconnections
+        let relay_publisher_monitor_socket = Transport::create_monitor_socket(&self.context, &relay_publisher_socket, "publisher")?;
 
         let mut items = [
             event_bus_sub_socket.as_poll_item(zmq::POLLIN),
             engine_subscriber_socket.as_poll_item(zmq::POLLIN),
+            relay_publisher_monitor_socket.as_poll_item(zmq::POLLIN),
         ];
 
         self.is_running = true;
         while self.is_running {
-            // Poll both sockets
+            // Poll all sockets
             if zmq::poll(&mut items, -1).is_ok() {
                 // Check for event bus messages
                 if items[0].is_readable() {
@@ -40,7 +50,11 @@ impl EngineMessageProxy {
 
                 // Check for engine SUB messages (if running)
                 if items[1].is_readable() && self.is_running {
-                    self.forward_engine_message(&engine_subscriber_socket, &relay_publisher_socket);
+                    self.forward_engine_message(&engine_subscriber_socket, &relay_publisher_socket, transport);
+                }
+
+                if items[2].is_readable() {
+                    Transport::read_monitor_event(&relay_publisher_monitor_socket, "publisher");
                 }
             }
         }
@@ -50,30 +64,30 @@ impl EngineMessageProxy {
         Ok(())
     }
 
-    fn create_relay_publisher_socket(&self, port: u32) -> Result<zmq::Socket> {
+    fn create_relay_publisher_socket(&self, bind_address: &str, port: u32, keepalive: Option<&KeepaliveSettings>, hwm: i32) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::PUB)?;
         socket.set_linger(0)?;
-        let address = format!("tcp://*:{}", port);
+        Transport::apply_hwm(&socket, hwm)?;
+        Transport::apply_keepalive(&socket, keepalive)?;
+        socket.set_ipv6(Transport::is_ipv6_address(bind_address))?;
+        let address = Transport::tcp_address(bind_address, port);
         match socket.bind(address.as_str()) {
             Ok(_) => debug!("Message Proxy bound to {}", address),
-            Err(error) => {
-                error!("Failed to bind PUB socket to {}: {}", address, error);
-                process::exit(1);
-            }
+            Err(error) => return Err(Transport::bind_error(&format!("publisher port {}", port), error))
         }
 
         Ok(socket)
     }
 
-    fn create_engine_subscriber_socket(&self, path: &str) -> Result<zmq::Socket> {
+    fn create_engine_subscriber_socket(&self, path: &str, hwm: i32) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::SUB)?;
         // Listen on all topics
         socket.set_subscribe(b"")?;
         socket.set_linger(0)?;
+        Transport::apply_hwm(&socket, hwm)?;
         let address = format!("ipc://{}", path);
         if let Err(error) = socket.bind(address.as_str()) {
-            error!("Failed to bind engine SUB socket to {}: {}", address, error);
-            process::exit(1);
+            return Err(Transport::bind_error(&format!("engine message proxy socket {}", address), error));
         }
 
         // Make sure socket is accessible only to current user
@@ -99,7 +113,7 @@ impl EngineMessageProxy {
         }
     }
 
-    fn forward_engine_message(&self, engine_subscriber_socket: &zmq::Socket, relay_publisher_socket: &zmq::Socket) {
+    fn forward_engine_message(&self, engine_subscriber_socket: &zmq::Socket, relay_publisher_socket: &zmq::Socket, transport: &TransportSettings) {
         let mut msg = zmq::Message::new();
 
         // Get message on subscriber socket
@@ -107,11 +121,22 @@ impl EngineMessageProxy {
             error!("Failed to received message from engine message publisher: {}", error);
 
         } else {
-            trace!("Got message from engine of length {}", msg.len());
-            // Resend message on publisher socket
-            if let Err(error) = relay_publisher_socket.send(msg, 0) {
+            Transport::trace_message("Got message from engine", &msg, engine_subscriber_socket, transport.trace_sample_rate());
+
+            // Resend message on publisher socket (compressed if required)
+            let result = if transport.compress_relay {
+                relay_publisher_socket.send(Transport::compress_message(&msg, transport.compression_threshold_bytes()), 0)
+
+            } else {
+                relay_publisher_socket.send(msg, 0)
+            };
+
+            if let Err(error) = result {
                 error!("Failed to send message to relay message subscriber: {}", error);
-            }   
+
+            } else {
+                self.metrics.inc_relay_messages_forwarded();
+            }
         }
     }
 
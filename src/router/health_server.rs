@@ -0,0 +1,148 @@
+use crate::common::*;
+use crate::router::{HttpHandler, HttpListener};
+
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+// Maximum time without the Session Proxy completing a loop before the router is considered unhealthy.
+// The event bus only forwards messages and has no heartbeat of its own so it is not monitored
+static MAX_SESSION_PROXY_HEARTBEAT_AGE_S: u64 = 60;
+
+pub struct HealthServer {
+    context: zmq::Context,
+    metrics: Arc<Metrics>,
+    tls_paths: Option<(String, String)>,
+    tls_config: Option<Arc<ServerConfig>>,
+}
+
+impl HealthServer {
+
+    pub fn new(context: zmq::Context, metrics: Arc<Metrics>, health_settings: &HealthSettings) -> Self {
+        Self {
+            context,
+            metrics,
+            tls_paths: health_settings.tls_paths().map(|(cert_path, key_path)| (cert_path.to_string(), key_path.to_string())),
+            tls_config: None,
+        }
+    }
+
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC, INPROC_CONFIG_TOPIC])
+    }
+
+    pub fn run(&mut self, bind_address: &str, port: u32, event_bus_sub_socket: zmq::Socket) -> Result<()> {
+        let listener = HttpListener::bind("health", bind_address, port)?;
+
+        if let Some((cert_path, key_path)) = &self.tls_paths {
+            self.tls_config = Some(HealthServer::load_tls_config(cert_path, key_path)?);
+            debug!("Health Server using TLS certificate {}", cert_path);
+        }
+
+        listener.run(self, &event_bus_sub_socket);
+
+        debug!("Stopped Health Server");
+
+        Ok(())
+    }
+
+    fn load_tls_config(cert_path: &str, key_path: &str) -> Result<Arc<ServerConfig>> {
+        let certificates = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+            .into_iter()
+            .map(Certificate)
+            .collect::<Vec<Certificate>>();
+        if certificates.is_empty() {
+            return Err(RouterError::TransportError(format!("No certificate found in {}", cert_path)));
+        }
+
+        // Accept PKCS8, RSA and EC private keys
+        let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+                _ => None
+            });
+        let key = match key {
+            Some(key) => key,
+            None => return Err(RouterError::TransportError(format!("No private key found in {}", key_path)))
+        };
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certificates, key)
+            .map_err(|error| RouterError::TransportError(format!("Invalid health TLS certificate: {}", error)))?;
+
+        Ok(Arc::new(config))
+    }
+
+    fn reload_tls_config(&mut self) {
+        // Certificates that have been renewed are re-read from the same files
+        if let Some((cert_path, key_path)) = &self.tls_paths {
+            match HealthServer::load_tls_config(cert_path, key_path) {
+                Ok(tls_config) => {
+                    self.tls_config = Some(tls_config);
+                    info!("Health Server TLS certificate reloaded");
+                },
+                Err(error) => error!("Failed to reload health TLS certificate, keeping current one: {}", error),
+            }
+        }
+    }
+
+    fn handle_request<S: Read + Write>(&self, stream: &mut S) {
+        let mut request_line = String::new();
+        if let Err(error) = BufReader::new(&mut *stream).read_line(&mut request_line) {
+            debug!("Failed to read health request: {}", error);
+            return;
+        }
+
+        let response = if request_line.starts_with("GET /healthz ") {
+            let heartbeat_age_s = self.metrics.session_proxy_heartbeat_age_s();
+            if heartbeat_age_s > MAX_SESSION_PROXY_HEARTBEAT_AGE_S {
+                let body = format!("{{\"status\":\"unavailable\",\"reason\":\"session proxy unresponsive for {} seconds\"}}", heartbeat_age_s);
+                HttpListener::response("503 Service Unavailable", "application/json", &body)
+
+            } else {
+                let body = format!("{{\"status\":\"ok\",\"sessions\":{}}}", self.metrics.sessions_active());
+                HttpListener::response("200 OK", "application/json", &body)
+            }
+
+        } else {
+            HttpListener::not_found_response()
+        };
+
+        if let Err(error) = stream.write_all(response.as_bytes()) {
+            debug!("Failed to send health response: {}", error);
+        }
+    }
+}
+
+impl HttpHandler for HealthServer {
+
+    fn handle_connection(&mut self, mut stream: TcpStream) {
+        match &self.tls_config {
+            Some(tls_config) => match ServerConnection::new(tls_config.clone()) {
+                // The TLS handshake is performed when the request is read
+                Ok(connection) => {
+                    let mut tls_stream = StreamOwned::new(connection, stream);
+                    self.handle_request(&mut tls_stream);
+                    tls_stream.conn.send_close_notify();
+                    let _ = tls_stream.flush();
+                },
+                Err(error) => warn!("Failed to create health TLS connection: {}", error),
+            },
+            None => self.handle_request(&mut stream),
+        }
+    }
+
+    fn handle_event(&mut self, event: &str) {
+        if event == CONFIG_RELOAD_COMMAND {
+            self.reload_tls_config();
+
+        } else {
+            warn!("Got unknown event bus command: {}", event);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::common::*;
+use crate::router::Transport;
+
+use std::io::{self, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+static CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub trait HttpHandler {
+    fn handle_connection(&mut self, stream: TcpStream);
+    fn handle_event(&mut self, event: &str);
+}
+
+pub struct HttpListener {
+    name: &'static str,
+    listener: TcpListener,
+}
+
+impl HttpListener {
+
+    pub fn bind(name: &'static str, bind_address: &str, port: u32) -> Result<Self> {
+        let address = Transport::socket_address(bind_address, port);
+        let listener = match TcpListener::bind(&address) {
+            Ok(listener) => listener,
+            Err(error) => return Err(RouterError::TransportError(format!("Failed to bind {} port {}: {}", name, port, error)))
+        };
+        listener.set_nonblocking(true)?;
+        debug!("HTTP {} listener bound to {}", name, address);
+
+        Ok(Self {
+            name,
+            listener,
+        })
+    }
+
+    // Handles connections and event bus messages until the application shuts down
+    pub fn run<H: HttpHandler>(&self, handler: &mut H, event_bus_sub_socket: &zmq::Socket) {
+        let mut items = [
+            event_bus_sub_socket.as_poll_item(zmq::POLLIN),
+        ];
+
+        let mut is_running = true;
+        while is_running {
+            // The listener is non-blocking so the event bus is polled between connections
+            match self.listener.accept() {
+                Ok((stream, _)) => match HttpListener::configure_stream(&stream) {
+                    Ok(_) => handler.handle_connection(stream),
+                    Err(error) => warn!("Failed to configure {} connection: {}", self.name, error),
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {},
+                Err(error) => warn!("Failed to accept {} connection: {}", self.name, error),
+            }
+
+            if zmq::poll(&mut items, 100).is_ok() && items[0].is_readable() {
+                is_running = self.read_event_bus(handler, event_bus_sub_socket);
+            }
+        }
+    }
+
+    pub fn response(status: &str, content_type: &str, body: &str) -> String {
+        format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body)
+    }
+
+    pub fn not_found_response() -> String {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    }
+
+    fn configure_stream(stream: &TcpStream) -> io::Result<()> {
+        // Connections are handled one at a time: time out reads and writes so that a slow client cannot block the server
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))
+    }
+
+    fn read_event_bus<H: HttpHandler>(&self, handler: &mut H, event_bus_sub_socket: &zmq::Socket) -> bool {
+        let mut msg = zmq::Message::new();
+
+        if let Err(error) = event_bus_sub_socket.recv(&mut msg, 0) {
+            error!("Failed to receive event bus message: {}", error);
+
+        } else {
+            let event = msg.as_str().unwrap();
+            if event == APPLICATION_SHUTDOWN_COMMAND {
+                return false;
+            }
+
+            handler.handle_event(event);
+        }
+
+        true
+    }
+}
@@ -0,0 +1,61 @@
+use crate::common::*;
+use crate::router::{HttpHandler, HttpListener};
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+pub struct MetricsServer {
+    context: zmq::Context,
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsServer {
+
+    pub fn new(context: zmq::Context, metrics: Arc<Metrics>) -> Self {
+        Self {
+            context,
+            metrics,
+        }
+    }
+
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])
+    }
+
+    pub fn run(&mut self, bind_address: &str, port: u32, event_bus_sub_socket: zmq::Socket) -> Result<()> {
+        let listener = HttpListener::bind("metrics", bind_address, port)?;
+
+        listener.run(self, &event_bus_sub_socket);
+
+        debug!("Stopped Metrics Server");
+
+        Ok(())
+    }
+}
+
+impl HttpHandler for MetricsServer {
+
+    fn handle_connection(&mut self, mut stream: TcpStream) {
+        let mut request_line = String::new();
+        if let Err(error) = BufReader::new(&stream).read_line(&mut request_line) {
+            debug!("Failed to read metrics request: {}", error);
+            return;
+        }
+
+        let response = if request_line.starts_with("GET /metrics ") {
+            HttpListener::response("200 OK", "text/plain; version=0.0.4", &self.metrics.render())
+
+        } else {
+            HttpListener::not_found_response()
+        };
+
+        if let Err(error) = stream.write_all(response.as_bytes()) {
+            debug!("Failed to send metrics response: {}", error);
+        }
+    }
+
+    fn handle_event(&mut self, event: &str) {
+        warn!("Got unknown event bus command: {}", event);
+    }
+}
@@ -3,9 +3,18 @@ pub use client_connector::ClientConnector;
 pub use engine_message_proxy::EngineMessageProxy;
 pub use relay_instruction_proxy::RelayInstructionProxy;
 pub use session_proxy::SessionProxy;
+pub use metrics_server::MetricsServer;
+pub use health_server::HealthServer;
+pub use http_listener::{HttpHandler, HttpListener};
+pub use curve_key_store::CurveKeyStore;
 
 mod transport;
 mod client_connector;
 mod engine_message_proxy;
 mod relay_instruction_proxy;
 mod session_proxy;
+mod session_protocol;
+mod metrics_server;
+mod health_server;
+mod http_listener;
+mod curve_key_store;
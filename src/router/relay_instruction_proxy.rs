@@ -1,41 +1,50 @@
 use crate::common::*;
-use std::process;
+use crate::router::Transport;
 use std::ops::Deref;
+use std::sync::Arc;
 use hex;
 
 pub struct RelayInstructionProxy {
     context: zmq::Context,
+    metrics: Arc<Metrics>,
     is_running: bool,
 }
 
 impl RelayInstructionProxy {
 
-    pub fn new(context: zmq::Context) -> Self {
+    pub fn new(context: zmq::Context, metrics: Arc<Metrics>) -> Self {
         Self {
             context,
+            metrics,
             is_running: false,
         }
     }
 
-    pub fn run(&mut self, settings: &Settings) -> Result<()> {
-        let transport = &settings.transport;
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])
+    }
 
-        let relay_sub_socket = self.create_relay_sub_socket(transport.ports.collector)?;
+    pub fn run(&mut self, settings: &Settings, event_bus_sub_socket: zmq::Socket) -> Result<()> {
+        let transport = &settings.transport;
 
-        let engine_pub_socket = self.create_engine_pub_socket(&transport.ipc.instruction_proxy)?;
+        let relay_sub_socket = self.create_relay_sub_socket(transport.collector_bind_address(), transport.ports.collector, transport.keepalive.as_ref(), transport.hwm.instruction_proxy())?;
 
-        let event_bus_sub_socket = EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC])?;
+        let engine_pub_socket = self.create_engine_pub_socket(&transport.ipc.instruction_proxy, transport.hwm.instruction_proxy())?;
 
         let event_bus_pub_socket = EventBus::create_event_publisher(&self.context)?;
 
+        // Log relay connections and disconnections
+        let relay_sub_monitor_socket = Transport::create_monitor_socket(&self.context, &relay_sub_socket, "collector")?;
+
         let mut items = [
             event_bus_sub_socket.as_poll_item(zmq::POLLIN),
             relay_sub_socket.as_poll_item(zmq::POLLIN),
+            relay_sub_monitor_socket.as_poll_item(zmq::POLLIN),
         ];
 
         self.is_running = true;
         while self.is_running {
-            // Poll both sockets
+            // Poll all sockets
             if zmq::poll(&mut items, -1).is_ok() {
                 // Check for message_bus messages
                 if items[0].is_readable() {
@@ -44,7 +53,7 @@ impl RelayInstructionProxy {
 
                 // Check for relay PUB messages (if running)
                 if items[1].is_readable() && self.is_running {
-                    match self.forward_relay_instruction(&relay_sub_socket, &engine_pub_socket) {
+                    match self.forward_relay_instruction(&relay_sub_socket, &engine_pub_socket, transport) {
                         // Send session id on inproc message queue, to be used by session_proxy
                         Some(session_id) => {
                             let session_message = format!("{}:{}", INPROC_SESSION_TOPIC, session_id);
@@ -53,6 +62,10 @@ impl RelayInstructionProxy {
                         None => {}
                     }
                 }
+
+                if items[2].is_readable() {
+                    Transport::read_monitor_event(&relay_sub_monitor_socket, "collector");
+                }
             }
         }
 
@@ -61,31 +74,31 @@ impl RelayInstructionProxy {
         Ok(())
     }
 
-    fn create_relay_sub_socket(&self, port: u32) -> Result<zmq::Socket> {
+    fn create_relay_sub_socket(&self, bind_address: &str, port: u32, keepalive: Option<&KeepaliveSettings>, hwm: i32) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::SUB)?;
         // Listen on all topics
         socket.set_subscribe(b"")?;
         socket.set_linger(0)?;
-        let address = format!("tcp://*:{}", port);
+        Transport::apply_hwm(&socket, hwm)?;
+        Transport::apply_keepalive(&socket, keepalive)?;
+        socket.set_ipv6(Transport::is_ipv6_address(bind_address))?;
+        let address = Transport::tcp_address(bind_address, port);
 
         match socket.bind(address.as_str()) {
             Ok(_) => debug!("Instruction Proxy bound to {}", address),
-            Err(error) => {
-                error!("Failed to bind relay SUB socket to {}: {}", address, error);
-                process::exit(1);
-            }
+            Err(error) => return Err(Transport::bind_error(&format!("collector port {}", port), error))
         }
 
         Ok(socket)
     }
 
-    fn create_engine_pub_socket(&self, path: &str) -> Result<zmq::Socket> {
+    fn create_engine_pub_socket(&self, path: &str, hwm: i32) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::PUB)?;
         socket.set_linger(0)?;
+        Transport::apply_hwm(&socket, hwm)?;
         let address = format!("ipc://{}", path);
         if let Err(error) = socket.bind(address.as_str()) {
-            error!("Failed to bind engine PUB socket to {}: {}", address, error);
-            process::exit(1);
+            return Err(Transport::bind_error(&format!("engine instruction proxy socket {}", address), error));
         }
 
         // Make sure socket is accessible only to current user
@@ -111,7 +124,7 @@ impl RelayInstructionProxy {
         }
     }
 
-    fn forward_relay_instruction(&self, relay_sub_socket: &zmq::Socket, engine_pub_socket: &zmq::Socket) -> Option<String> {
+    fn forward_relay_instruction(&self, relay_sub_socket: &zmq::Socket, engine_pub_socket: &zmq::Socket, transport: &TransportSettings) -> Option<String> {
         let mut msg = zmq::Message::new();
         let mut session_id_option = None;
 
@@ -120,7 +133,18 @@ impl RelayInstructionProxy {
             error!("Failed to received instruction from relay publisher: {}", error);
 
         } else {
-            trace!("Got instruction from relay of length {}", msg.len());
+            Transport::trace_message("Got instruction from relay", &msg, relay_sub_socket, transport.trace_sample_rate());
+
+            // Decompress instruction if required
+            if transport.compress_relay {
+                match Transport::decompress_message(&msg) {
+                    Ok(decompressed_message) => msg = zmq::Message::from(decompressed_message),
+                    Err(error) => {
+                        warn!("Dropping instruction from relay: {}", error);
+                        return None;
+                    }
+                }
+            }
 
             // Get session_id from the msg
             let raw_session_id = msg.deref();
@@ -130,7 +154,10 @@ impl RelayInstructionProxy {
             // Resend message on engine pub socket
             if let Err(error) = engine_pub_socket.send(msg, 0) {
                 error!("Failed to send instruction to engine subscribers: {}", error);
-            }   
+
+            } else {
+                self.metrics.inc_instruction_messages_forwarded();
+            }
         }
 
         session_id_option
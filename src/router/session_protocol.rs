@@ -0,0 +1,108 @@
+use crate::common::*;
+
+use serde::{Deserialize, Serialize};
+
+// Binary requests are sent as two frames: a command type byte followed by a JSON payload.
+// Legacy text requests always start with a printable character
+pub const BINARY_COMMAND_LIMIT: u8 = 0x20;
+
+pub const ADMIN_ONLY_COMMAND_ERROR: &str = "Command only available on the admin socket";
+
+const CREATE_COMMAND: u8 = 0x01;
+const PING_COMMAND: u8 = 0x02;
+const LIST_COMMAND: u8 = 0x03;
+const COUNT_COMMAND: u8 = 0x04;
+const DELETE_COMMAND: u8 = 0x05;
+const RESIZE_COMMAND: u8 = 0x06;
+const WHO_COMMAND: u8 = 0x07;
+const SUSPEND_COMMAND: u8 = 0x08;
+const RESUME_COMMAND: u8 = 0x09;
+const CONNECT_COMMAND: u8 = 0x0a;
+const DISCONNECT_COMMAND: u8 = 0x0b;
+const STATUS_COMMAND: u8 = 0x0c;
+
+#[derive(Deserialize)]
+pub struct CreatePayload {
+    pub username: String,
+    pub password: String,
+    // WIDTHxHEIGHT or a preset name, the default resolution is used if missing
+    pub resolution: Option<String>,
+    pub keyboard: String,
+    #[serde(default)]
+    pub tenant: String,
+}
+
+#[derive(Deserialize)]
+pub struct PingPayload {
+    pub session_id: Option<String>,
+}
+
+pub enum SessionCommand {
+    Create(CreatePayload),
+    Ping(PingPayload),
+}
+
+impl SessionCommand {
+
+    pub fn decode(command_type: u8, payload: &[u8]) -> Result<Self> {
+        match command_type {
+            CREATE_COMMAND => Ok(SessionCommand::Create(serde_json::from_slice(payload)?)),
+            PING_COMMAND => Ok(SessionCommand::Ping(serde_json::from_slice(payload)?)),
+            // Session management commands are not authenticated so they are only accepted on the admin socket
+            LIST_COMMAND | COUNT_COMMAND | DELETE_COMMAND | RESIZE_COMMAND | WHO_COMMAND | SUSPEND_COMMAND | RESUME_COMMAND | CONNECT_COMMAND | DISCONNECT_COMMAND | STATUS_COMMAND => Err(RouterError::SessionError(ADMIN_ONLY_COMMAND_ERROR.to_string())),
+            _ => Err(RouterError::SessionError(format!("Unknown session command type {:#04x}", command_type)))
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub version: Option<String>,
+    pub tenant: String,
+    pub username: String,
+    pub uid: u32,
+    pub display: String,
+    pub width: u32,
+    pub height: u32,
+    pub created_at: u64,
+    pub last_activity: u64,
+    pub suspended: bool,
+    pub connections: u32,
+}
+
+impl SessionInfo {
+
+    pub fn from_session(session: &Session) -> Self {
+        Self {
+            id: session.id().to_string(),
+            version: session.engine().version().map(|version| version.to_string()),
+            tenant: session.tenant().to_string(),
+            username: session.username().to_string(),
+            uid: session.uid(),
+            display: session.display_id().to_string(),
+            width: session.resolution().width(),
+            height: session.resolution().height(),
+            created_at: session.created_at(),
+            last_activity: session.last_activity(),
+            suspended: session.is_suspended(),
+            connections: session.connection_count(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SessionResponse {
+    Pong { session_id: Option<String> },
+    Created { session_id: String },
+    Error { message: String },
+}
+
+impl SessionResponse {
+
+    pub fn encode(&self) -> Vec<u8> {
+        // Serialising these types cannot fail
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+}
@@ -1,54 +1,145 @@
 use crate::common::*;
-use crate::service::SessionService;
+use crate::router::Transport;
+use crate::router::session_protocol::{SessionCommand, SessionResponse, SessionInfo, ADMIN_ONLY_COMMAND_ERROR, BINARY_COMMAND_LIMIT};
+use crate::service::{SessionService, SessionStore, SessionMonitor, AuditLogger};
 
+use nix::sys::signal::{raise, Signal};
+use std::collections::{HashMap, VecDeque};
 use std::str;
-use std::process;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
+static RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+static SHUTDOWN_ACTIVITY_WINDOW_S: u64 = 10;
+
+// Commands that are only accepted on the admin socket, which is protected by file permissions
+static ADMIN_ONLY_COMMANDS: [&str; 10] = ["list", "count", "delete", "suspend", "resume", "connect", "disconnect", "status", "resize", "who"];
+
 pub struct SessionProxy {
     context: zmq::Context,
     service: SessionService,
+    metrics: Arc<Metrics>,
+    // Recent session creation times by source IP and username
+    session_creation_times: HashMap<(String, String), VecDeque<Instant>>,
     is_running: bool,
+    is_settings_reload_required: bool,
+    is_shutting_down: bool,
+    shutdown_deadline: Option<Instant>,
 }
 
 impl SessionProxy {
 
-    pub fn new(context: zmq::Context) -> Self {
+    pub fn new(context: zmq::Context, metrics: Arc<Metrics>, audit_logger: Option<AuditLogger>) -> Self {
         Self {
             context,
-            service: SessionService::new(),
+            service: SessionService::new(metrics.clone(), audit_logger),
+            metrics,
+            session_creation_times: HashMap::new(),
             is_running: false,
+            is_settings_reload_required: false,
+            is_shutting_down: false,
+            shutdown_deadline: None,
         }
     }
 
-    pub fn run(&mut self, settings: &Settings) -> Result<()> {
+    pub fn create_event_bus_sub_socket(&self) -> Result<zmq::Socket> {
+        EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC, INPROC_SESSION_TOPIC, INPROC_CONFIG_TOPIC])
+    }
+
+    pub fn run(&mut self, shared_settings: &Arc<RwLock<Settings>>, event_bus_sub_socket: zmq::Socket) -> Result<()> {
+        let mut settings = shared_settings.read().unwrap().clone();
         let transport = &settings.transport;
 
-        let secure_rep_socket = self.create_secure_rep_socket(transport.ports.session, &transport.encryption.private)?;
+        let secure_rep_socket = self.create_secure_rep_socket(transport.session_bind_address(), transport.ports.session, &transport.encryption.private, transport.keepalive.as_ref())?;
+
+        let event_bus_pub_socket = EventBus::create_event_publisher(&self.context)?;
+
+        // Optional unencrypted socket for local administration
+        let admin_rep_socket = match &transport.admin_socket_path {
+            Some(admin_socket_path) => Some(self.create_admin_rep_socket(admin_socket_path, transport.admin_socket_group())?),
+            None => None,
+        };
 
-        let event_bus_sub_socket = EventBus::create_event_subscriber(&self.context, &[INPROC_APP_TOPIC, INPROC_SESSION_TOPIC])?;
+        // Optional socket publishing session lifecycle events to local monitors
+        if let Some(monitor_socket_path) = &transport.monitor_socket_path {
+            self.service.set_session_monitor(SessionMonitor::new(&self.context, monitor_socket_path, transport.admin_socket_group())?);
+        }
+
+        // Recover sessions from a previous router instance
+        if let Some(session_store_path) = &settings.sesman.session_store_path {
+            self.service.restore_sessions(SessionStore::new(session_store_path), &settings, &self.context);
+        }
 
-        let mut items = [
+        let mut items = vec![
             event_bus_sub_socket.as_poll_item(zmq::POLLIN),
             secure_rep_socket.as_poll_item(zmq::POLLIN),
         ];
+        if let Some(admin_rep_socket) = &admin_rep_socket {
+            items.push(admin_rep_socket.as_poll_item(zmq::POLLIN));
+        }
 
         self.is_running = true;
         while self.is_running {
+            self.metrics.update_session_proxy_heartbeat();
+
             // Poll both sockets
             if zmq::poll(&mut items, 5000).is_ok() {
                 // Check for event bus messages
                 if items[0].is_readable() {
-                    self.read_event_bus(&event_bus_sub_socket);
+                    self.read_event_bus(&event_bus_sub_socket, &settings);
+                }
+
+                // Take a new copy of the settings if they have been reloaded
+                if self.is_settings_reload_required {
+                    settings = shared_settings.read().unwrap().clone();
+                    self.is_settings_reload_required = false;
+                    info!("Session Proxy settings reloaded");
                 }
 
                 // Check for session REQ messages (if running)
                 if items[1].is_readable() && self.is_running {
-                    self.handle_secure_request(&secure_rep_socket, settings);
+                    self.handle_secure_request(&secure_rep_socket, &settings);
+                }
+
+                // Check for local admin REQ messages (if running)
+                if let Some(admin_rep_socket) = &admin_rep_socket {
+                    if items[2].is_readable() && self.is_running {
+                        self.handle_admin_request(admin_rep_socket, &event_bus_pub_socket, &settings);
+                    }
+                }
+
+                // Shutdown once clients have disconnected during a graceful shutdown
+                if self.is_running && self.is_graceful_shutdown_complete() {
+                    info!("Graceful shutdown period over: shutting down");
+                    self.shutdown_deadline = None;
+                    if let Err(error) = event_bus_pub_socket.send(APPLICATION_SHUTDOWN_COMMAND, 0) {
+                        error!("Failed to send shutdown command: {}", error);
+                    }
+                }
+
+                // Restart engines that have stopped unexpectedly
+                self.service.restart_crashed_engines(&settings, &self.context);
+
+                // Ping idle sessions to detect dead engines
+                self.service.ping_idle_sessions(&settings, &self.context);
+
+                // Cleanup sessions that have reached their maximum duration and warn of those about to
+                for session_id in self.service.cleanup_expired_sessions(&settings, &self.context) {
+                    let expiring_message = format!("{},{}", SESSION_EXPIRING_EVENT, session_id);
+                    if let Err(error) = event_bus_pub_socket.send(&expiring_message, 0) {
+                        error!("Failed to send session expiring event: {}", error);
+                    }
                 }
 
                 // Cleanup inactive sessions
-                self.service.cleanup_inactive_sessions(settings, &self.context);
+                self.service.cleanup_inactive_sessions(&settings, &self.context);
+
+                // Cleanup sessions of users that no longer exist
+                self.service.cleanup_deleted_user_sessions(&settings, &self.context);
+
+                // Rotate engine logs that have grown too large
+                self.service.rotate_engine_logs(&settings);
             }
         }
 
@@ -57,28 +148,46 @@ impl SessionProxy {
         Ok(())
     }
 
-    fn create_secure_rep_socket(&self, port: u32, secret_key_string: &str) -> Result<zmq::Socket> {
+    fn create_secure_rep_socket(&self, bind_address: &str, port: u32, secret_key_string: &str, keepalive: Option<&KeepaliveSettings>) -> Result<zmq::Socket> {
         let socket = self.context.socket(zmq::REP)?;
         socket.set_linger(0)?;
+        Transport::apply_keepalive(&socket, keepalive)?;
 
         // Secure the socket 
         let secret_key = zmq::z85_decode(secret_key_string)?;
         socket.set_curve_server(true)?;
         socket.set_curve_secretkey(&secret_key)?;
 
-        let address = format!("tcp://*:{}", port);
+        socket.set_ipv6(Transport::is_ipv6_address(bind_address))?;
+        let address = Transport::tcp_address(bind_address, port);
         match socket.bind(address.as_str()) {
             Ok(_) => debug!("Session Proxy bound to {}", address),
-            Err(error) => {
-                error!("Failed to bind Session Proxy socket to {}: {}", address, error);
-                process::exit(1);
-            }
+            Err(error) => return Err(Transport::bind_error(&format!("session port {}", port), error))
         }
 
         Ok(socket)
     }
 
-    fn read_event_bus(&mut self, event_bus_sub_socket: &zmq::Socket) {
+    fn create_admin_rep_socket(&self, path: &str, group: &str) -> Result<zmq::Socket> {
+        let socket = self.context.socket(zmq::REP)?;
+        socket.set_linger(0)?;
+
+        let address = format!("ipc://{}", path);
+        match socket.bind(address.as_str()) {
+            Ok(_) => debug!("Session Proxy admin socket bound to {}", address),
+            Err(error) => return Err(RouterError::TransportError(format!("Failed to bind admin socket to {}: {}", address, error)))
+        }
+
+        // Requests are not authenticated so access is restricted by the file permissions
+        System::chmod(path, 0o660)?;
+        if let Err(error) = System::chgrp(path, group) {
+            warn!("Admin socket {} is only accessible by the router user: {}", path, error);
+        }
+
+        Ok(socket)
+    }
+
+    fn read_event_bus(&mut self, event_bus_sub_socket: &zmq::Socket, settings: &Settings) {
         let mut msg = zmq::Message::new();
 
         if let Err(error) = event_bus_sub_socket.recv(&mut msg, 0) {
@@ -90,7 +199,13 @@ impl SessionProxy {
                 self.is_running = false;
 
                 // Close all sessions gracefully
-                self.service.stop_sessions();
+                self.service.stop_sessions(settings);
+
+            } else if event == SESSION_SHUTDOWN_REQUEST {
+                self.start_graceful_shutdown(settings);
+
+            } else if event.starts_with(SESSION_EXPIRING_EVENT) {
+                // Published by this proxy for other subscribers
 
             } else if event.starts_with(INPROC_SESSION_TOPIC) {
                 let message_text = msg.as_str().unwrap();
@@ -98,12 +213,35 @@ impl SessionProxy {
                 let session_id = message_parts[1];
                 self.service.update_session_activity(session_id);
 
+            } else if event == CONFIG_RELOAD_COMMAND {
+                self.is_settings_reload_required = true;
+
             } else {
                 warn!("Got unknown event bus command: {}", event);
             }
         }
     }
 
+    fn start_graceful_shutdown(&mut self, settings: &Settings) {
+        if self.is_shutting_down {
+            return;
+        }
+        self.is_shutting_down = true;
+
+        let grace_period_s = settings.sesman.shutdown_grace_period_s;
+        info!("Shutdown requested: waiting up to {}s for clients to disconnect", grace_period_s);
+        self.service.notify_shutdown(grace_period_s, settings, &self.context);
+        self.shutdown_deadline = Some(Instant::now() + Duration::from_secs(grace_period_s));
+    }
+
+    fn is_graceful_shutdown_complete(&self) -> bool {
+        match self.shutdown_deadline {
+            // Clients are considered disconnected when their sessions no longer receive instructions
+            Some(deadline) => Instant::now() >= deadline || self.service.get_active_sessions(SHUTDOWN_ACTIVITY_WINDOW_S).is_empty(),
+            None => false,
+        }
+    }
+
     fn handle_secure_request(&mut self, secure_rep_socket: &zmq::Socket, settings: &Settings) {
         let mut msg = zmq::Message::new();
 
@@ -113,6 +251,15 @@ impl SessionProxy {
             return;
         }
 
+        // Get the address of the client for auditing
+        let source_ip = msg.gets("Peer-Address").unwrap_or("unknown").to_string();
+
+        // Binary requests contain a single command type byte followed by a JSON payload frame
+        if msg.len() == 1 && msg[0] < BINARY_COMMAND_LIMIT {
+            self.handle_binary_request(secure_rep_socket, msg[0], settings, &source_ip);
+            return;
+        }
+
         // Decode message
         let mut send_empty = true;
         let message_text = msg.as_str().unwrap();
@@ -129,10 +276,12 @@ impl SessionProxy {
 
             } else {
                 let session_id = message_parts[1];
-                debug!("Got ping for session {}", session_id);
 
                 // Ping the session and get a string response
-                let ping_response = self.ping_session(&session_id);
+                let ping_response = match self.ping_session(session_id, settings) {
+                    Ok(_) => format!("pong,{}", session_id),
+                    Err(error) => format!("pang,{},{}", session_id, error),
+                };
                 if let Err(error) = secure_rep_socket.send(ping_response.as_str(), 0) {
                     error!("Failed to send session ping message: {}", error);
                 }
@@ -140,29 +289,30 @@ impl SessionProxy {
             send_empty = false;
 
         } else if message_parts[0] == "create" {
-            match self.decode_create_command(&message_parts) {
-                Ok((username, password, width, height, keyboard)) => {
-                    info!("Got session create command for user \"{}\"", username);
-
-                    // Request session from WebX Session Manager
-                    let message = self.get_or_create_session(settings, &username, &password, width, height, &keyboard);
-
-                    // Send message response
-                    if let Err(error) = secure_rep_socket.send(message.as_str(), 0) {
-                        error!("Failed to send session creation response: {}", error);
-                    }
-                    send_empty = false;
+            let message = match self.decode_create_command(&message_parts, settings) {
+                Ok(request) => match self.create_session(settings, &request, &source_ip) {
+                    Ok(session_id) => format!("0,{}", session_id),
+                    Err(error) => format!("1,{}", error),
                 },
                 Err(error) => {
                     error!("Failed to decode create command: {}", error);
-                    
-                    // Send error response
-                    if let Err(error) = secure_rep_socket.send(format!("1,{}", error).as_str(), 0) {
-                        error!("Failed to send session creation error response: {}", error);
-                    }
-                    send_empty = false;
+                    format!("1,{}", error)
                 }
+            };
+
+            // Send message response
+            if let Err(error) = secure_rep_socket.send(message.as_str(), 0) {
+                error!("Failed to send session creation response: {}", error);
+            }
+            send_empty = false;
+
+        } else if ADMIN_ONLY_COMMANDS.contains(&message_parts[0]) {
+            // The session port is reachable by any client with the public key: commands giving access to other users' sessions are refused
+            warn!("Refusing {} command from {} on the session port", message_parts[0], source_ip);
+            if let Err(error) = secure_rep_socket.send(format!("1,{}", ADMIN_ONLY_COMMAND_ERROR).as_str(), 0) {
+                error!("Failed to send session {} response: {}", message_parts[0], error);
             }
+            send_empty = false;
 
         } else {
             error!("Got unknown session command");
@@ -177,42 +327,385 @@ impl SessionProxy {
         }
     }
 
-    fn get_or_create_session(&mut self, settings: &Settings, username: &str, password: &str, width: u32, height: u32, keyboard: &str) -> String {
-        match self.service.get_or_create_session(settings, username, password, width, height, keyboard, &self.context) {
-            Ok(session) => format!("0,{}", session.id()),
+    fn handle_admin_request(&mut self, admin_rep_socket: &zmq::Socket, event_bus_pub_socket: &zmq::Socket, settings: &Settings) {
+        let mut msg = zmq::Message::new();
+
+        if let Err(error) = admin_rep_socket.recv(&mut msg, 0) {
+            error!("Failed to received message on admin request socket: {}", error);
+            return;
+        }
+
+        let message = self.execute_admin_command(msg.as_str().unwrap_or(""), event_bus_pub_socket, settings);
+
+        if let Err(error) = admin_rep_socket.send(message.as_str(), 0) {
+            error!("Failed to send admin response: {}", error);
+        }
+    }
+
+    fn execute_admin_command(&mut self, message_text: &str, event_bus_pub_socket: &zmq::Socket, settings: &Settings) -> String {
+        // Requests are not authenticated: only local users with access to the socket file can send them
+        let message_parts = message_text.split(',').collect::<Vec<&str>>();
+        match message_parts[0] {
+            "ping" if message_parts.len() == 1 => "pong".to_string(),
+            "status" if message_parts.len() == 1 => format!("sessions={},active={},shutting_down={}",
+                self.service.get_sessions().len(),
+                self.service.get_active_sessions(settings.sesman.who_activity_window_s()).len(),
+                self.is_shutting_down),
+            "status" if message_parts.len() == 2 => match self.get_session_status(message_parts[1], settings) {
+                Ok((session_info, engine_status)) => format!("0,{},engine_status={}", self.format_session_info(&session_info), engine_status),
+                Err(error) => format!("1,{}", error),
+            },
+            "list" => self.list_sessions(&message_parts),
+            "who" => self.list_active_sessions(settings),
+            "count" => self.service.get_sessions().len().to_string(),
+            "delete" if message_parts.len() == 2 => match self.delete_session(message_parts[1], settings) {
+                Ok(_) => "ok".to_string(),
+                Err(error) => format!("1,{}", error),
+            },
+            "suspend" | "resume" if message_parts.len() == 2 => match self.set_session_suspended(message_parts[1], message_parts[0] == "suspend", settings) {
+                Ok(_) => "0".to_string(),
+                Err(error) => format!("1,{}", error),
+            },
+            "connect" | "disconnect" if message_parts.len() == 2 => match self.update_session_connections(message_parts[1], message_parts[0] == "connect", settings) {
+                Ok(connections) => format!("0,{}", connections),
+                Err(error) => format!("1,{}", error),
+            },
+            "resize" if message_parts.len() == 4 => match self.resize_session(message_parts[1], message_parts[2], message_parts[3], settings) {
+                Ok(_) => "0".to_string(),
+                Err(error) => format!("1,{}", error),
+            },
+            "engine_logs" if message_parts.len() == 2 => match self.service.get_session(message_parts[1]) {
+                Some(session) => session.engine().log_paths().join("\n"),
+                None => format!("1,Could not retrieve Session with ID \"{}\"", message_parts[1]),
+            },
+            "shutdown" if message_parts.len() == 1 => {
+                info!("Shutdown requested on admin socket");
+                match event_bus_pub_socket.send(APPLICATION_SHUTDOWN_COMMAND, 0) {
+                    Ok(_) => "ok".to_string(),
+                    Err(error) => format!("1,{}", error),
+                }
+            },
+            "reload" if message_parts.len() == 1 => {
+                // Handled in the same way as a SIGHUP sent to the router
+                match raise(Signal::SIGHUP) {
+                    Ok(_) => "ok".to_string(),
+                    Err(error) => format!("1,{}", error),
+                }
+            },
+            _ => {
+                error!("Got unknown admin command");
+                format!("1,Unknown admin command \"{}\"", message_text)
+            }
+        }
+    }
+
+    fn handle_binary_request(&mut self, secure_rep_socket: &zmq::Socket, command_type: u8, settings: &Settings, source_ip: &str) {
+        // Get the JSON payload frame
+        let mut payload = zmq::Message::new();
+        let response = if !secure_rep_socket.get_rcvmore().unwrap_or(false) {
+            SessionResponse::Error { message: "Missing session command payload".to_string() }
+
+        } else if let Err(error) = secure_rep_socket.recv(&mut payload, 0) {
+            error!("Failed to receive session command payload: {}", error);
+            SessionResponse::Error { message: error.to_string() }
+
+        } else {
+            match SessionCommand::decode(command_type, &payload) {
+                Ok(command) => self.execute_command(command, settings, source_ip),
+                Err(error) => {
+                    error!("Failed to decode session command: {}", error);
+                    SessionResponse::Error { message: error.to_string() }
+                }
+            }
+        };
+
+        // Discard any unexpected frames so that the response can be sent
+        while secure_rep_socket.get_rcvmore().unwrap_or(false) {
+            if secure_rep_socket.recv_bytes(0).is_err() {
+                break;
+            }
+        }
+
+        if let Err(error) = secure_rep_socket.send(response.encode(), 0) {
+            error!("Failed to send session command response: {}", error);
+        }
+    }
+
+    fn execute_command(&mut self, command: SessionCommand, settings: &Settings, source_ip: &str) -> SessionResponse {
+        let result = match command {
+            SessionCommand::Create(payload) => {
+                self.decode_resolution(payload.resolution.as_deref().unwrap_or(""), settings)
+                    .and_then(|resolution| {
+                        let request = SessionRequest::new(payload.username, payload.password, resolution, payload.keyboard, payload.tenant);
+                        self.create_session(settings, &request, source_ip)
+                    })
+                    .map(|session_id| SessionResponse::Created { session_id })
+            },
+            SessionCommand::Ping(payload) => match payload.session_id {
+                Some(session_id) => self.ping_session(&session_id, settings).map(|_| SessionResponse::Pong { session_id: Some(session_id) }),
+                None => Ok(SessionResponse::Pong { session_id: None }),
+            },
+        };
+
+        result.unwrap_or_else(|error| SessionResponse::Error { message: error.to_string() })
+    }
+
+    fn create_session(&mut self, settings: &Settings, request: &SessionRequest, source_ip: &str) -> Result<String> {
+        info!("Got session create command for user \"{}\" with resolution {}", request.username(), request.resolution());
+
+        // Request session from WebX Session Manager (unless the user has exceeded the creation rate)
+        if let Err(error) = settings.sesman.validate_resolution(request.resolution()).and_then(|_| self.check_creation_allowed(request.username(), source_ip, settings)) {
+            warn!("Refusing session creation for user \"{}\": {}", request.username(), error);
+            return Err(error);
+        }
+
+        match self.service.get_or_create_session(settings, request, source_ip, &self.context) {
+            Ok(session) => Ok(session.id().to_string()),
             Err(error) => {
-                error!("Failed to create session for user {}: {}", username, error);
-                format!("1,{}", error)
+                error!("Failed to create session for user {}: {}", request.username(), error);
+                self.metrics.inc_sessions_failed();
+                match error {
+                    // Clients can retry sessions that are slow to start
+                    RouterError::TimeoutError(_) => Err(RouterError::SessionError("Session creation timed out, please try again".to_string())),
+                    error => Err(error),
+                }
             }
         }
     }
 
-    fn ping_session(&mut self, session_id: &str) -> String {
-        match self.service.ping_session(session_id, &self.context) {
-            Ok(_) => format!("pong,{}", session_id),
+    fn delete_session(&mut self, session_id: &str, settings: &Settings) -> Result<()> {
+        let _log_context = SessionLogContext::new(session_id);
+        info!("Got delete command for session {}", session_id);
+        self.service.delete_session(session_id, settings, &self.context).map_err(|error| {
+            error!("Failed to delete session with id {}: {}", session_id, error);
+            error
+        })
+    }
+
+    fn set_session_suspended(&mut self, session_id: &str, is_suspended: bool, settings: &Settings) -> Result<()> {
+        let _log_context = SessionLogContext::new(session_id);
+        self.service.set_session_suspended(session_id, is_suspended, settings, &self.context).map_err(|error| {
+            error!("Failed to {} session with id {}: {}", if is_suspended { "suspend" } else { "resume" }, session_id, error);
+            error
+        })
+    }
+
+    fn update_session_connections(&mut self, session_id: &str, is_connecting: bool, settings: &Settings) -> Result<u32> {
+        let _log_context = SessionLogContext::new(session_id);
+        let result = if is_connecting {
+            self.service.connect_session(session_id, settings, &self.context)
+        } else {
+            self.service.disconnect_session(session_id, settings, &self.context)
+        };
+
+        result.map_err(|error| {
+            error!("Failed to {} session with id {}: {}", if is_connecting { "connect to" } else { "disconnect from" }, session_id, error);
+            error
+        })
+    }
+
+    fn get_session_status(&mut self, session_id: &str, settings: &Settings) -> Result<(SessionInfo, EngineStatus)> {
+        let _log_context = SessionLogContext::new(session_id);
+        debug!("Got status command for session {}", session_id);
+        let engine_status = self.service.get_engine_status(session_id, settings, &self.context).map_err(|error| {
+            error!("Failed to get status of session with id {}: {}", session_id, error);
+            error
+        })?;
+
+        match self.service.get_session(session_id) {
+            Some(session) => Ok((SessionInfo::from_session(session), engine_status)),
+            None => Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        }
+    }
+
+    fn resize_session(&mut self, session_id: &str, width: &str, height: &str, settings: &Settings) -> Result<()> {
+        let _log_context = SessionLogContext::new(session_id);
+        info!("Got resize command for session {}", session_id);
+        let result = format!("{}x{}", width, height).parse::<ScreenResolution>()
+            .and_then(|resolution| self.service.resize_session(session_id, &resolution, settings, &self.context));
+
+        result.map_err(|error| {
+            error!("Failed to resize session with id {}: {}", session_id, error);
+            error
+        })
+    }
+
+    fn check_creation_allowed(&mut self, username: &str, source_ip: &str, settings: &Settings) -> Result<()> {
+        if self.is_shutting_down {
+            return Err(RouterError::SessionError("WebX Router is shutting down".to_string()));
+        }
+
+        self.check_creation_rate_limit(username, source_ip, settings)
+    }
+
+    fn check_creation_rate_limit(&mut self, username: &str, source_ip: &str, settings: &Settings) -> Result<()> {
+        let max_creates_per_minute = match &settings.sesman.rate_limit {
+            Some(rate_limit) => rate_limit.max_creates_per_minute as usize,
+            None => return Ok(()),
+        };
+
+        // Requests are counted before authentication: including the source IP prevents other clients from locking a user out
+        let now = Instant::now();
+        let creation_times = self.session_creation_times.entry((source_ip.to_string(), username.to_string())).or_insert_with(VecDeque::new);
+
+        // Expire creation times outside of the sliding window
+        while creation_times.front().map_or(false, |time| now.duration_since(*time) > RATE_LIMIT_WINDOW) {
+            creation_times.pop_front();
+        }
+
+        if creation_times.len() >= max_creates_per_minute {
+            return Err(RouterError::SessionError(format!("Session creation rate limit of {} per minute exceeded", max_creates_per_minute)));
+        }
+
+        creation_times.push_back(now);
+
+        // Remove clients that no longer have any recent creations
+        self.session_creation_times.retain(|_, creation_times| creation_times.back().map_or(false, |time| now.duration_since(*time) <= RATE_LIMIT_WINDOW));
+
+        Ok(())
+    }
+
+    fn ping_session(&mut self, session_id: &str, settings: &Settings) -> Result<()> {
+        let _log_context = SessionLogContext::new(session_id);
+        debug!("Got ping for session {}", session_id);
+        self.service.ping_session(session_id, settings, &self.context).map_err(|error| {
+            error!("Failed to ping session with id {}: {}", session_id, error);
+            error
+        })
+    }
+
+    fn get_sessions_page(&self, tenant: Option<&str>, offset: usize, limit: usize) -> (usize, Vec<&Session>) {
+        // Optionally only include the sessions of a tenant
+        let sessions: Vec<&Session> = self.service.get_sessions()
+            .iter()
+            .filter(|session| tenant.map_or(true, |tenant| session.tenant() == tenant))
+            .collect();
+
+        let total_count = sessions.len();
+        let page = sessions.into_iter().skip(offset).take(limit).collect();
+
+        (total_count, page)
+    }
+
+    fn list_sessions(&self, message_parts: &Vec<&str>) -> String {
+        // Optionally only list the sessions of a tenant
+        let mut message_parts = message_parts.clone();
+        let tenant = match message_parts.last().and_then(|part| part.strip_prefix("tenant=")) {
+            Some(tenant) => {
+                let tenant = tenant.to_string();
+                message_parts.pop();
+                Some(tenant)
+            },
+            None => None
+        };
+
+        // Get the optional page of sessions
+        let (offset, limit) = match self.decode_list_command(&message_parts) {
+            Ok(Some((offset, limit))) => (offset, limit),
+            Ok(None) => (0, usize::MAX),
             Err(error) => {
-                error!("Failed to ping session with id {}: {}", session_id, error);
-                format!("pang,{},{}", session_id, error)
+                error!("Failed to decode list command: {}", error);
+                return format!("1,{}", error);
             }
+        };
+
+        let (total_count, sessions) = self.get_sessions_page(tenant.as_deref(), offset, limit);
+
+        // First line contains the total count so that clients know when to stop paginating
+        let mut lines = vec![format!("total_count={}", total_count)];
+        for session in sessions {
+            lines.push(self.format_session_info(&SessionInfo::from_session(session)));
+        }
+
+        lines.join("\n")
+    }
+
+    fn format_session_info(&self, session_info: &SessionInfo) -> String {
+        format!("id={},version={},tenant={},username={},uid={},display={},width={},height={},connections={},created_at={}",
+            session_info.id,
+            session_info.version.as_deref().unwrap_or("unknown"),
+            session_info.tenant,
+            session_info.username,
+            session_info.uid,
+            session_info.display,
+            session_info.width,
+            session_info.height,
+            session_info.connections,
+            session_info.created_at)
+    }
+
+    fn list_active_sessions(&self, settings: &Settings) -> String {
+        // Sessions that have received instructions from a relay within the activity window
+        let active_sessions = self.service.get_active_sessions(settings.sesman.who_activity_window_s());
+
+        let mut lines = vec![format!("total_count={}", active_sessions.len())];
+        for session in active_sessions {
+            lines.push(format!("id={},username={},display={},last_activity={}",
+                session.id(),
+                session.username(),
+                session.display_id(),
+                session.last_activity()));
+        }
+
+        lines.join("\n")
+    }
+
+    fn decode_list_command(&self, message_parts: &Vec<&str>) -> Result<Option<(usize, usize)>> {
+        match message_parts.len() {
+            1 => Ok(None),
+            3 => {
+                let offset = message_parts[1].to_string().parse::<usize>()?;
+                let limit = message_parts[2].to_string().parse::<usize>()?;
+                Ok(Some((offset, limit)))
+            },
+            _ => Err(RouterError::SessionError(format!("Incorrect number of parameters. Got {}, expected 1 or 3", message_parts.len())))
         }
     }
 
-    fn decode_create_command(&self, message_parts: &Vec<&str>) -> Result<(String, String, u32, u32, String)> {
-        if message_parts.len() == 6 {
-            let username_base64 = message_parts[1];
-            let password_base64 = message_parts[2];
-            let username = self.decode_base64(username_base64)?;
-            let password = self.decode_base64(password_base64)?;
+    fn decode_create_command(&self, message_parts: &Vec<&str>, settings: &Settings) -> Result<SessionRequest> {
+        if message_parts.len() < 5 || message_parts.len() > 7 {
+            return Err(RouterError::SessionError(format!("Incorrect number of parameters. Got {}, expected 5 to 7", message_parts.len())));
+        }
 
+        // Accept either the legacy width,height pair or a single WIDTHxHEIGHT field (a width is numeric)
+        let is_legacy_resolution = message_parts.len() > 5 && message_parts[3].parse::<u32>().is_ok();
+        let (resolution, keyboard_index) = if is_legacy_resolution {
             let width = message_parts[3].to_string().parse::<u32>()?;
             let height = message_parts[4].to_string().parse::<u32>()?;
-            let keyboard = message_parts[5].to_string();
-
-            Ok((username, password, width, height, keyboard))
+            (ScreenResolution::new(width, height), 5)
 
         } else {
-            Err(RouterError::SessionError(format!("Incorrect number of parameters. Got {}, expected 6", message_parts.len())))
+            (self.decode_resolution(message_parts[3], settings)?, 4)
+        };
+
+        // The tenant is optional and follows the keyboard layout
+        let keyboard = match message_parts.get(keyboard_index) {
+            Some(keyboard) => keyboard.to_string(),
+            None => return Err(RouterError::SessionError("Missing keyboard layout parameter".to_string()))
+        };
+        let tenant = match message_parts.len() - keyboard_index {
+            1 => String::new(),
+            2 => message_parts[keyboard_index + 1].to_string(),
+            _ => return Err(RouterError::SessionError(format!("Incorrect number of parameters. Got {}", message_parts.len())))
+        };
+
+        let username_base64 = message_parts[1];
+        let password_base64 = message_parts[2];
+        let username = self.decode_base64(username_base64)?;
+        let password = self.decode_base64(password_base64)?;
+
+        Ok(SessionRequest::new(username, password, resolution, keyboard, tenant))
+    }
+
+    fn decode_resolution(&self, value: &str, settings: &Settings) -> Result<ScreenResolution> {
+        // Use the default resolution if none is specified
+        if value.is_empty() {
+            if let Some(default_resolution) = settings.sesman.default_resolution() {
+                return default_resolution;
+            }
         }
+
+        value.parse::<ScreenResolution>()
     }
 
     fn decode_base64(&self, input: &str) -> Result<String> {
@@ -223,3 +716,136 @@ impl SessionProxy {
         Ok(output.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEST_SETTINGS: &str = "
+logging: info
+transport:
+  ports:
+    connector: 5555
+    publisher: 5556
+    collector: 5557
+    session: 5558
+  ipc:
+    message_proxy: /tmp/webx-router-message-proxy.ipc
+    instruction_proxy: /tmp/webx-router-instruction-proxy.ipc
+    engine_connector_root: /tmp/webx-engine-session-connector
+    sesman_connector: /tmp/webx-session-manager.ipc
+  encryption:
+    public: ''
+    private: ''
+sesman:
+  enabled: false
+  fallback_display_id: ':0'
+  auto_logout_s: 600
+engine:
+  path: /usr/bin/webx-engine
+  logdir: /var/log/webx/engine
+";
+
+    fn create_settings() -> Settings {
+        // Sessions are not logged out of the WebX Session Manager when it is disabled
+        serde_yaml::from_str(TEST_SETTINGS).unwrap()
+    }
+
+    fn create_session_proxy(session_count: usize) -> SessionProxy {
+        let mut session_proxy = SessionProxy::new(zmq::Context::new(), Arc::new(Metrics::new()), None);
+        for index in 0..session_count {
+            let session = Session::for_test(&format!("session-{}", index), &format!("user{}", index), 1000 + index as u32, &format!(":{}", 10 + index));
+            session_proxy.service.add_session(session);
+        }
+        session_proxy
+    }
+
+    fn list_sessions(session_proxy: &SessionProxy, command: &str) -> Vec<String> {
+        let message_parts: Vec<&str> = command.split(',').collect();
+        session_proxy.list_sessions(&message_parts).lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn creation_rate_limit_is_per_source_ip() {
+        let mut settings = create_settings();
+        settings.sesman.rate_limit = serde_yaml::from_str("max_creates_per_minute: 1").unwrap();
+        let mut session_proxy = create_session_proxy(0);
+
+        assert!(session_proxy.check_creation_rate_limit("alice", "10.0.0.1", &settings).is_ok());
+        assert!(session_proxy.check_creation_rate_limit("alice", "10.0.0.1", &settings).is_err());
+
+        // Requests for the same user from another client are not refused
+        assert!(session_proxy.check_creation_rate_limit("alice", "10.0.0.2", &settings).is_ok());
+    }
+
+    #[test]
+    fn binary_admin_commands_are_refused() {
+        for command_type in 0x03..=0x0c {
+            match SessionCommand::decode(command_type, b"{}") {
+                Err(RouterError::SessionError(message)) => assert_eq!(message, ADMIN_ONLY_COMMAND_ERROR),
+                _ => panic!("command type {:#04x} accepted on the session port", command_type),
+            }
+        }
+    }
+
+    #[test]
+    fn list_without_sessions_returns_total_count_only() {
+        let settings = create_settings();
+        let mut session_proxy = create_session_proxy(0);
+        let event_bus_pub_socket = session_proxy.context.socket(zmq::PUB).unwrap();
+
+        assert_eq!(list_sessions(&session_proxy, "list"), vec!["total_count=0"]);
+        assert_eq!(list_sessions(&session_proxy, "list,0,2"), vec!["total_count=0"]);
+        assert_eq!(session_proxy.execute_admin_command("count", &event_bus_pub_socket, &settings), "0");
+    }
+
+    #[test]
+    fn list_with_one_session_returns_session() {
+        let settings = create_settings();
+        let mut session_proxy = create_session_proxy(1);
+        let event_bus_pub_socket = session_proxy.context.socket(zmq::PUB).unwrap();
+
+        let lines = list_sessions(&session_proxy, "list");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "total_count=1");
+        assert!(lines[1].starts_with("id=session-0,"), "unexpected session line {}", lines[1]);
+        assert_eq!(session_proxy.execute_admin_command("count", &event_bus_pub_socket, &settings), "1");
+
+        session_proxy.service.stop_sessions(&settings);
+    }
+
+    #[test]
+    fn list_pages_contain_total_count_of_all_sessions() {
+        let settings = create_settings();
+        let mut session_proxy = create_session_proxy(5);
+        let event_bus_pub_socket = session_proxy.context.socket(zmq::PUB).unwrap();
+
+        assert_eq!(list_sessions(&session_proxy, "list").len(), 6);
+
+        let first_page = list_sessions(&session_proxy, "list,0,2");
+        assert_eq!(first_page.len(), 3);
+        assert_eq!(first_page[0], "total_count=5");
+        assert!(first_page[1].starts_with("id=session-0,"));
+        assert!(first_page[2].starts_with("id=session-1,"));
+
+        let last_page = list_sessions(&session_proxy, "list,4,2");
+        assert_eq!(last_page.len(), 2);
+        assert_eq!(last_page[0], "total_count=5");
+        assert!(last_page[1].starts_with("id=session-4,"));
+
+        assert_eq!(list_sessions(&session_proxy, "list,6,2"), vec!["total_count=5"]);
+        assert_eq!(session_proxy.execute_admin_command("count", &event_bus_pub_socket, &settings), "5");
+
+        session_proxy.service.stop_sessions(&settings);
+    }
+
+    #[test]
+    fn admin_delete_of_unknown_session_returns_error() {
+        let context = zmq::Context::new();
+        let event_bus_pub_socket = context.socket(zmq::PUB).unwrap();
+        let mut session_proxy = SessionProxy::new(context, Arc::new(Metrics::new()), None);
+
+        let response = session_proxy.execute_admin_command("delete,unknown", &event_bus_pub_socket, &create_settings());
+        assert!(response.starts_with("1,"), "unexpected response {}", response);
+    }
+}
@@ -1,7 +1,16 @@
-use crate::router::{EngineMessageProxy, RelayInstructionProxy, ClientConnector, SessionProxy};
+use crate::router::{EngineMessageProxy, RelayInstructionProxy, ClientConnector, SessionProxy, MetricsServer, HealthServer, CurveKeyStore};
 use crate::common::*;
+use crate::service::AuditLogger;
 
+use std::net::Ipv6Addr;
+use std::sync::{Arc, RwLock};
 use std::thread;
+use rand::Rng;
+
+static SESSION_ID_LENGTH: usize = 16;
+const UNCOMPRESSED_FLAG: u8 = 0;
+const LZ4_COMPRESSED_FLAG: u8 = 1;
+static MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
 
 pub struct Transport {
     context: zmq:: Context,
@@ -15,74 +24,360 @@ impl Transport {
         }
     }
 
-    pub fn run(&self, settings: &mut Settings) -> Result<()> {
-        let transport = &mut settings.transport;
+    pub fn run(&self, shared_settings: &Arc<RwLock<Settings>>) -> Result<()> {
+        let settings = {
+            let mut settings = shared_settings.write().unwrap();
+            let transport = &mut settings.transport;
 
-        // Check for public/private keys in settings
-        if transport.encryption.private.is_empty() || transport.encryption.public.is_empty() {
-            let server_pair = zmq::CurveKeyPair::new()?;
-            let public_key_string = zmq::z85_encode(&server_pair.public_key).unwrap();
-            let secret_key_string = zmq::z85_encode(&server_pair.secret_key).unwrap();
+            // Check for public/private keys in settings
+            if transport.encryption.private.is_empty() || transport.encryption.public.is_empty() {
+                // Keys persisted in a key file are kept across restarts
+                let (public_key_string, secret_key_string) = match &transport.encryption.key_path {
+                    Some(key_path) => CurveKeyStore::new(key_path).load_or_generate()?,
+                    None => {
+                        info!("Encyption keys not set in application config: generating new ones");
+                        CurveKeyStore::generate()?
+                    }
+                };
 
-            info!("Encyption keys not set in application config: generating new ones");
-            transport.encryption.public = public_key_string;
-            transport.encryption.private = secret_key_string;
-        }
+                transport.encryption.public = public_key_string;
+                transport.encryption.private = secret_key_string;
+            }
+
+            settings.clone()
+        };
+        let settings = &settings;
+        let transport = &settings.transport;
+
+        // Create all components and their event bus subscribers before any of them starts: a startup failure
+        // in one component (eg port already in use) is then guaranteed to be received by all the others
+        let metrics = Arc::new(Metrics::new());
+
+        let engine_message_proxy = EngineMessageProxy::new(self.context.clone(), metrics.clone());
+        let engine_message_proxy_sub_socket = engine_message_proxy.create_event_bus_sub_socket()?;
+
+        let relay_instruction_proxy = RelayInstructionProxy::new(self.context.clone(), metrics.clone());
+        let relay_instruction_proxy_sub_socket = relay_instruction_proxy.create_event_bus_sub_socket()?;
+
+        let audit_logger = match &settings.logging.audit {
+            Some(audit_settings) => Some(AuditLogger::new(&audit_settings.path)?),
+            None => None
+        };
+        let session_proxy = SessionProxy::new(self.context.clone(), metrics.clone(), audit_logger);
+        let session_proxy_sub_socket = session_proxy.create_event_bus_sub_socket()?;
+
+        let mut client_connector = ClientConnector::new(self.context.clone());
+        let client_connector_sub_socket = client_connector.create_event_bus_sub_socket()?;
+
+        let health_server = match transport.ports.health {
+            Some(_) => {
+                let health_server = HealthServer::new(self.context.clone(), metrics.clone(), &transport.health);
+                let health_server_sub_socket = health_server.create_event_bus_sub_socket()?;
+                Some((health_server, health_server_sub_socket))
+            },
+            None => None
+        };
+
+        let metrics_server = match transport.ports.metrics {
+            Some(_) => {
+                let metrics_server = MetricsServer::new(self.context.clone(), metrics);
+                let metrics_server_sub_socket = metrics_server.create_event_bus_sub_socket()?;
+                Some((metrics_server, metrics_server_sub_socket))
+            },
+            None => None
+        };
 
         // Create and run the engine message proxy in separate thread
-        let engine_message_proxy_thread = self.create_engine_message_proxy_thread(self.context.clone(), settings);
+        let engine_message_proxy_thread = self.create_engine_message_proxy_thread(engine_message_proxy, engine_message_proxy_sub_socket, settings)?;
 
         // Create and run the relay instruction proxy in separate thread
-        let relay_instruction_proxy_thread = self.create_relay_instruction_proxy_thread(self.context.clone(), settings);
+        let relay_instruction_proxy_thread = self.create_relay_instruction_proxy_thread(relay_instruction_proxy, relay_instruction_proxy_sub_socket, settings)?;
 
         // Create and run the session proxy in separate thread
-        let session_proxy_thread = self.create_session_proxy_thread(self.context.clone(), settings);
+        let session_proxy_thread = self.create_session_proxy_thread(session_proxy, session_proxy_sub_socket, shared_settings)?;
 
-        // Create and run the Client Connector in the current thread (blocking)
-        if let Err(error) = ClientConnector::new(self.context.clone()).run(settings) {
+        // Create and run the metrics server in separate thread if enabled
+        let metrics_server_thread = match metrics_server {
+            Some((metrics_server, metrics_server_sub_socket)) => Some(self.create_metrics_server_thread(metrics_server, metrics_server_sub_socket, settings)?),
+            None => None
+        };
+
+        // Create and run the health server in separate thread if enabled
+        let health_server_thread = match health_server {
+            Some((health_server, health_server_sub_socket)) => Some(self.create_health_server_thread(health_server, health_server_sub_socket, settings)?),
+            None => None
+        };
+
+        // Run the Client Connector in the current thread (blocking)
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        let mut result = client_connector.run(settings, client_connector_sub_socket);
+        if let Err(error) = &result {
             error!("Error while running Client Connector: {}", error);
+            Transport::send_shutdown(&shutdown_publisher);
         }
 
         // Join engine message proxy thread
-        engine_message_proxy_thread.join().unwrap();
+        result = result.and(engine_message_proxy_thread.join().unwrap());
 
         // Join relay instruction proxy thread
-        relay_instruction_proxy_thread.join().unwrap();
+        result = result.and(relay_instruction_proxy_thread.join().unwrap());
 
-        // Join relay instruction proxy thread
-        session_proxy_thread.join().unwrap();
+        // Join session proxy thread
+        result = result.and(session_proxy_thread.join().unwrap());
+
+        // Join metrics server thread
+        if let Some(metrics_server_thread) = metrics_server_thread {
+            result = result.and(metrics_server_thread.join().unwrap());
+        }
+
+        // Join health server thread
+        if let Some(health_server_thread) = health_server_thread {
+            result = result.and(health_server_thread.join().unwrap());
+        }
+
+        result
+    }
+
+    pub fn is_ipv6_address(bind_address: &str) -> bool {
+        bind_address.parse::<Ipv6Addr>().is_ok()
+    }
+
+    pub fn tcp_address(bind_address: &str, port: u32) -> String {
+        // IPv6 addresses must be enclosed in brackets
+        if Transport::is_ipv6_address(bind_address) {
+            format!("tcp://[{}]:{}", bind_address, port)
+
+        } else {
+            format!("tcp://{}:{}", bind_address, port)
+        }
+    }
+
+    pub fn socket_address(bind_address: &str, port: u32) -> String {
+        if bind_address == "*" {
+            format!("0.0.0.0:{}", port)
+
+        } else if Transport::is_ipv6_address(bind_address) {
+            format!("[{}]:{}", bind_address, port)
+
+        } else {
+            format!("{}:{}", bind_address, port)
+        }
+    }
+
+    // Must be applied before binding for the options to be used by accepted connections
+    pub fn apply_keepalive(socket: &zmq::Socket, keepalive: Option<&KeepaliveSettings>) -> Result<()> {
+        if let Some(keepalive) = keepalive {
+            socket.set_tcp_keepalive(if keepalive.enabled { 1 } else { 0 })?;
+            socket.set_tcp_keepalive_idle(keepalive.idle_s.unwrap_or(-1))?;
+            socket.set_tcp_keepalive_intvl(keepalive.interval_s.unwrap_or(-1))?;
+            socket.set_tcp_keepalive_cnt(keepalive.count.unwrap_or(-1))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_hwm(socket: &zmq::Socket, hwm: i32) -> Result<()> {
+        socket.set_sndhwm(hwm)?;
+        socket.set_rcvhwm(hwm)?;
 
         Ok(())
     }
 
-    fn create_engine_message_proxy_thread(&self, context: zmq::Context, settings: &Settings) -> thread::JoinHandle<()>{
-        thread::spawn({
+    pub fn bind_error(description: &str, error: zmq::Error) -> RouterError {
+        if error == zmq::Error::EADDRINUSE {
+            RouterError::TransportError(format!("Failed to bind {}: {}. Is another router instance running?", description, error))
+
+        } else {
+            RouterError::TransportError(format!("Failed to bind {}: {}", description, error))
+        }
+    }
+
+    // Monitor events are received on a PAIR socket connected to the monitored socket
+    pub fn create_monitor_socket(context: &zmq::Context, socket: &zmq::Socket, name: &str) -> Result<zmq::Socket> {
+        let address = format!("inproc://monitor-{}", name);
+        socket.monitor(&address, zmq::SocketEvent::ALL as i32)?;
+
+        let monitor_socket = context.socket(zmq::PAIR)?;
+        monitor_socket.set_linger(0)?;
+        monitor_socket.connect(&address)?;
+
+        Ok(monitor_socket)
+    }
+
+    pub fn read_monitor_event(monitor_socket: &zmq::Socket, name: &str) {
+        // Events contain a frame with the event type and value followed by one with the endpoint
+        let event_frame = match monitor_socket.recv_bytes(0) {
+            Ok(event_frame) => event_frame,
+            Err(error) => {
+                error!("Failed to receive {} monitor event: {}", name, error);
+                return;
+            }
+        };
+        let endpoint = match monitor_socket.get_rcvmore() {
+            Ok(true) => monitor_socket.recv_string(0).ok().and_then(|endpoint| endpoint.ok()).unwrap_or_default(),
+            _ => String::new(),
+        };
+
+        if event_frame.len() < 2 {
+            return;
+        }
+        let event = u16::from_ne_bytes([event_frame[0], event_frame[1]]);
+        if event == zmq::SocketEvent::DISCONNECTED.to_raw() {
+            warn!("Client disconnected from {} socket {}", name, endpoint);
+
+        } else if event == zmq::SocketEvent::CONNECT_RETRIED.to_raw() {
+            debug!("Retrying connection of {} socket {}", name, endpoint);
+
+        } else if event == zmq::SocketEvent::ACCEPTED.to_raw() {
+            debug!("Client connected to {} socket {}", name, endpoint);
+        }
+    }
+
+    pub fn trace_message(description: &str, message: &[u8], socket: &zmq::Socket, sample_rate: f32) {
+        // Only log a sample of the messages to avoid flooding the output
+        if !log_enabled!(log::Level::Trace) || rand::thread_rng().gen::<f32>() >= sample_rate {
+            return;
+        }
+
+        let identity = socket.get_identity().map(hex::encode).unwrap_or_default();
+        let header = hex::encode(&message[.. message.len().min(16)]);
+        if message.len() >= 16 {
+            // All instructions and messages are prefixed by the 16 byte session id
+            trace!("{} of length {} for session {} (socket identity \"{}\")", description, message.len(), header, identity);
+
+        } else {
+            trace!("{} of length {} starting with {} (socket identity \"{}\")", description, message.len(), header, identity);
+        }
+    }
+
+    // Compressed frames keep the 16 byte session id (used for subscription filtering) followed by a flag byte
+    // indicating whether the rest of the frame is LZ4 compressed. Small frames are not worth compressing
+    pub fn compress_message(message: &[u8], threshold: usize) -> Vec<u8> {
+        if message.len() < SESSION_ID_LENGTH {
+            return message.to_vec();
+        }
+
+        let (session_id, payload) = message.split_at(SESSION_ID_LENGTH);
+        let mut compressed_message = Vec::with_capacity(message.len() + 1);
+        compressed_message.extend_from_slice(session_id);
+        if payload.len() < threshold {
+            compressed_message.push(UNCOMPRESSED_FLAG);
+            compressed_message.extend_from_slice(payload);
+
+        } else {
+            compressed_message.push(LZ4_COMPRESSED_FLAG);
+            compressed_message.extend_from_slice(&lz4_flex::compress_prepend_size(payload));
+        }
+
+        compressed_message
+    }
+
+    pub fn decompress_message(message: &[u8]) -> Result<Vec<u8>> {
+        if message.len() <= SESSION_ID_LENGTH {
+            return Err(RouterError::TransportError(format!("Compressed frame too short ({} bytes)", message.len())));
+        }
+
+        let (session_id, flagged_payload) = message.split_at(SESSION_ID_LENGTH);
+        let payload = &flagged_payload[1..];
+        let mut decompressed_message = session_id.to_vec();
+        match flagged_payload[0] {
+            UNCOMPRESSED_FLAG => decompressed_message.extend_from_slice(payload),
+            LZ4_COMPRESSED_FLAG => {
+                // Check the prepended size before allocating the decompression buffer
+                if payload.len() < 4 {
+                    return Err(RouterError::TransportError("Compressed frame is missing its size".to_string()));
+                }
+                let size = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+                if size > MAX_DECOMPRESSED_SIZE {
+                    return Err(RouterError::TransportError(format!("Decompressed frame size {} is too large", size)));
+                }
+
+                match lz4_flex::decompress_size_prepended(payload) {
+                    Ok(decompressed_payload) => decompressed_message.extend_from_slice(&decompressed_payload),
+                    Err(error) => return Err(RouterError::TransportError(format!("Failed to decompress frame: {}", error))),
+                }
+            },
+            flag => return Err(RouterError::TransportError(format!("Unknown compression flag {}", flag))),
+        }
+
+        Ok(decompressed_message)
+    }
+
+    fn send_shutdown(shutdown_publisher: &zmq::Socket) {
+        // Stop all other components so that the error can be returned to the application
+        if let Err(error) = shutdown_publisher.send(APPLICATION_SHUTDOWN_COMMAND, 0) {
+            error!("Failed to send shutdown command: {}", error);
+        }
+    }
+
+    fn create_engine_message_proxy_thread(&self, mut engine_message_proxy: EngineMessageProxy, event_bus_sub_socket: zmq::Socket, settings: &Settings) -> Result<thread::JoinHandle<Result<()>>> {
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        Ok(thread::spawn({
             let settings = settings.clone();
             move || {
-            if let Err(error) = EngineMessageProxy::new(context).run(&settings) {
+            let result = engine_message_proxy.run(&settings, event_bus_sub_socket);
+            if let Err(error) = &result {
                 error!("Engine Message Proxy thread error: {}", error);
+                Transport::send_shutdown(&shutdown_publisher);
             }
-        }})
+            result
+        }}))
     }
 
-    fn create_relay_instruction_proxy_thread(&self, context: zmq::Context, settings: &Settings) -> thread::JoinHandle<()>{
-        thread::spawn({
+    fn create_relay_instruction_proxy_thread(&self, mut relay_instruction_proxy: RelayInstructionProxy, event_bus_sub_socket: zmq::Socket, settings: &Settings) -> Result<thread::JoinHandle<Result<()>>> {
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        Ok(thread::spawn({
             let settings = settings.clone();
             move || {
-            if let Err(error) = RelayInstructionProxy::new(context).run(&settings) {
+            let result = relay_instruction_proxy.run(&settings, event_bus_sub_socket);
+            if let Err(error) = &result {
                 error!("Relay Instruction Proxy thread error: {}", error);
+                Transport::send_shutdown(&shutdown_publisher);
             }
-        }})
+            result
+        }}))
     }
 
-    fn create_session_proxy_thread(&self, context: zmq::Context, settings: &Settings) -> thread::JoinHandle<()>{
-        thread::spawn({
+    fn create_session_proxy_thread(&self, mut session_proxy: SessionProxy, event_bus_sub_socket: zmq::Socket, settings: &Arc<RwLock<Settings>>) -> Result<thread::JoinHandle<Result<()>>> {
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        Ok(thread::spawn({
             let settings = settings.clone();
             move || {
-            if let Err(error) = SessionProxy::new(context).run(&settings) {
+            let result = session_proxy.run(&settings, event_bus_sub_socket);
+            if let Err(error) = &result {
                 error!("Session Proxy thread error: {}", error);
+                Transport::send_shutdown(&shutdown_publisher);
+            }
+            result
+        }}))
+    }
+
+    fn create_metrics_server_thread(&self, mut metrics_server: MetricsServer, event_bus_sub_socket: zmq::Socket, settings: &Settings) -> Result<thread::JoinHandle<Result<()>>> {
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        let bind_address = settings.transport.bind_address().to_string();
+        let port = settings.transport.ports.metrics.unwrap_or_default();
+        Ok(thread::spawn(move || {
+            let result = metrics_server.run(&bind_address, port, event_bus_sub_socket);
+            if let Err(error) = &result {
+                error!("Metrics Server thread error: {}", error);
+                Transport::send_shutdown(&shutdown_publisher);
+            }
+            result
+        }))
+    }
+
+    fn create_health_server_thread(&self, mut health_server: HealthServer, event_bus_sub_socket: zmq::Socket, settings: &Settings) -> Result<thread::JoinHandle<Result<()>>> {
+        let shutdown_publisher = EventBus::create_event_publisher(&self.context)?;
+        let bind_address = settings.transport.bind_address().to_string();
+        let port = settings.transport.ports.health.unwrap_or_default();
+        Ok(thread::spawn(move || {
+            let result = health_server.run(&bind_address, port, event_bus_sub_socket);
+            if let Err(error) = &result {
+                error!("Health Server thread error: {}", error);
+                Transport::send_shutdown(&shutdown_publisher);
             }
-        }})
+            result
+        }))
     }
 
 }
@@ -0,0 +1,64 @@
+use crate::common::*;
+
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent<'a> {
+    AuthAttempt { username: &'a str, source_ip: &'a str },
+    AuthSuccess { username: &'a str, session_id: &'a str },
+    AuthFailure { username: &'a str, reason: String },
+    SessionCreated { username: &'a str, session_id: &'a str },
+    SessionDestroyed { username: &'a str, session_id: &'a str },
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    #[serde(flatten)]
+    event: AuditEvent<'a>,
+}
+
+pub struct AuditLogger {
+    path: String,
+    file: File,
+}
+
+impl AuditLogger {
+
+    pub fn new(path: &str) -> Result<Self> {
+        if let Some(dir) = Path::new(path).parent() {
+            System::create_dir(dir, 0o700)?;
+        }
+
+        // The audit log is only ever appended to
+        let file = match OpenOptions::new().append(true).create(true).mode(0o600).open(path) {
+            Ok(file) => file,
+            Err(error) => return Err(RouterError::SystemError(format!("Failed to open audit log {}: {}", path, error))),
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    pub fn log(&self, event: AuditEvent) {
+        let record = AuditRecord {
+            timestamp: System::current_time_s(),
+            event,
+        };
+
+        let result = serde_json::to_string(&record)
+            .map_err(RouterError::from)
+            .and_then(|line| (&self.file).write_all(format!("{}\n", line).as_bytes()).map_err(RouterError::from));
+
+        if let Err(error) = result {
+            error!("Failed to write audit event to {}: {}", self.path, error);
+        }
+    }
+}
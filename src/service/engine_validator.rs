@@ -1,46 +1,101 @@
 use crate::common::*;
+use crate::service::SocketPool;
 
-pub struct EngineValidator {
+use std::thread;
+
+pub struct EngineValidator<'a> {
     context: zmq::Context,
+    socket_pool: &'a SocketPool,
+    max_response_size: usize,
+    pool_size: usize,
+    retry_policy: RetryPolicy,
 }
 
-impl EngineValidator {
+impl<'a> EngineValidator<'a> {
 
-    pub fn new(context: zmq::Context) -> Self {
+    pub fn new(context: zmq::Context, socket_pool: &'a SocketPool, settings: &EngineSettings, retry_policy: RetryPolicy) -> Self {
         Self {
             context,
+            socket_pool,
+            max_response_size: settings.max_response_size_bytes(),
+            pool_size: settings.socket_pool_size(),
+            retry_policy,
         }
     }
 
     pub fn validate_connection(&self, path: &str) -> Result<()> {
-        // Create REQ socket
-        let req_socket = self.create_req_socket(path)?;
-
         // Send ping message
         debug!("Pinging WebX Engine at {}", path);
-        if let Err(error) = req_socket.send("ping", 0) {
-            error!("Failed to send ping command to {}: {}", path, error);
-            return Err(RouterError::TransportError("Failed to send ping request".to_string()));
+        let message = self.send_request(path, "ping")?;
+        if message != "pong" {
+            error!("Received non-pong response from {}: {}", path, message);
+            return Err(RouterError::SessionError("Receivec non-pong message".to_string()));
+        }
+
+        debug!("Received pong response from {}", path);
+
+        Ok(())
+    }
+
+    pub fn send_request(&self, path: &str, request: &str) -> Result<String> {
+        let mut attempt = 0;
+        loop {
+            match self.try_send_request(path, request) {
+                Ok(response) => return Ok(response),
+                Err(error) if attempt + 1 < self.retry_policy.max_attempts => {
+                    // The engine may still be initialising: wait before retrying
+                    let delay = self.retry_policy.delay(attempt);
+                    debug!("Request {} to {} failed ({}), retrying in {}ms", request, path, error, delay.as_millis());
+                    thread::sleep(delay);
+                    attempt += 1;
+                },
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn try_send_request(&self, path: &str, request: &str) -> Result<String> {
+        // Reuse a connected REQ socket if available (sockets with errors are not returned to the pool)
+        let req_socket = match self.socket_pool.take(path) {
+            Some(socket) => socket,
+            None => self.create_req_socket(path)?,
+        };
+
+        if let Err(error) = req_socket.send(request, 0) {
+            error!("Failed to send {} command to {}: {}", request, path, error);
+            return Err(RouterError::TransportError(format!("Failed to send {} request", request)));
         }
 
-        trace!("Waiting for pong on WebX Engine at {}", path);
+        trace!("Waiting for response to {} on WebX Engine at {}", request, path);
         let mut response = zmq::Message::new();
         if let Err(error) = req_socket.recv(&mut response, 0) {
-            error!("Failed to receive response to ping on {}: {}", path, error);
-            return Err(RouterError::TransportError("Failed to received ping response".to_string()));
+            error!("Failed to receive response to {} on {}: {}", request, path, error);
+            return match error {
+                // Oversized responses are dropped with the connection by ZMQ_MAXMSGSIZE so they also end in a timeout
+                zmq::Error::EAGAIN => Err(RouterError::TransportError(format!("No {} response: the engine is not responding or its response is too large (maximum {} bytes)", request, self.max_response_size))),
+                _ => Err(RouterError::TransportError(format!("Failed to received {} response", request))),
+            };
         }
 
-        let message = response.as_str().unwrap();
-        if message != "pong" {
-            error!("Received non-pong response from {}: {}", path, message);
-            return Err(RouterError::SessionError("Receivec non-pong message".to_string()));
+        // Pooled sockets may have been created with a larger limit before the settings were reloaded
+        if response.len() > self.max_response_size {
+            warn!("Received response of {} bytes from {} exceeding the maximum of {} bytes", response.len(), path, self.max_response_size);
+            return Err(RouterError::TransportError("Engine response too large".to_string()));
         }
 
-        debug!("Received pong response from {}", path);
+        let message = match response.as_str() {
+            Some(message) => message.to_string(),
+            None => return Err(RouterError::TransportError("Received non UTF-8 engine response".to_string()))
+        };
 
-        self.disconnect_req_socket(&req_socket, path);
+        if self.pool_size > 0 {
+            self.socket_pool.put(path, req_socket, self.pool_size);
 
-        Ok(())
+        } else {
+            self.disconnect_req_socket(&req_socket, path);
+        }
+
+        Ok(message)
     }
 
     fn create_req_socket(&self, path: &str) -> Result<zmq::Socket> {
@@ -48,6 +103,9 @@ impl EngineValidator {
         socket.set_linger(0)?;
         socket.set_rcvtimeo(1000)?;
 
+        // Limit the size of the responses held in memory
+        socket.set_maxmsgsize(self.max_response_size as i64)?;
+
         let address = format!("ipc://{}", path);
         match socket.connect(address.as_str()) {
             Ok(_) => trace!("Engine Validator connected to {}", address),
@@ -1,7 +1,15 @@
 pub use session_service::SessionService;
 pub use engine_validator::EngineValidator;
 pub use sesman_connector::SesmanConnector;
+pub use session_store::SessionStore;
+pub use audit_logger::{AuditLogger, AuditEvent};
+pub use socket_pool::SocketPool;
+pub use session_monitor::{SessionMonitor, SessionMonitorEvent};
 
 mod session_service;
 mod engine_validator;
 mod sesman_connector;
+mod session_store;
+mod audit_logger;
+mod socket_pool;
+mod session_monitor;
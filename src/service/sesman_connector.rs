@@ -44,13 +44,15 @@ enum SessionManagerResponse {
 
 pub struct SesmanConnector {
     context: zmq::Context,
+    timeout_s: u64,
 }
 
 impl SesmanConnector {
 
-    pub fn new(context: zmq::Context) -> Self {
+    pub fn new(context: zmq::Context, timeout_s: u64) -> Self {
         Self {
             context,
+            timeout_s,
         }
     }
 
@@ -89,6 +91,10 @@ impl SesmanConnector {
         debug!("Waiting for X11 session login response");
         let mut response = zmq::Message::new();
         if let Err(error) = socket.recv(&mut response, 0) {
+            if error == zmq::Error::EAGAIN {
+                warn!("X11 session login request timed out after {} seconds", self.timeout_s);
+                return Err(RouterError::TimeoutError(format!("X11 session was not ready within {} seconds", self.timeout_s)));
+            }
             error!("Failed to receive response to X11 session login request: {}", error);
             return Err(RouterError::TransportError("Failed to receive X11 session login request response".to_string()));
         }
@@ -101,7 +107,7 @@ impl SesmanConnector {
             Ok(response) => match response {
                 SessionManagerResponse::Login(session) => {
                     debug!("X11 session request successful, got display Id: {}", &session.display_id);
-                    Ok(X11Session::new(session.id, session.username, session.display_id, session.xauthority_file_path))
+                    Ok(X11Session::new(session.id, session.username, session.uid, session.display_id, session.xauthority_file_path))
                 },
                 SessionManagerResponse::Error { message } => {
                     debug!("X11 session login request failed, got error: {}", &message);
@@ -167,6 +173,9 @@ impl SesmanConnector {
         let socket = self.context.socket(zmq::REQ)?;
         socket.set_linger(0)?;
 
+        // Do not wait indefinitely for the WebX Session Manager (eg authentication hanging)
+        socket.set_rcvtimeo((self.timeout_s * 1000) as i32)?;
+
         let address = format!("ipc://{}", path);
         match socket.connect(address.as_str()) {
             Ok(_) => debug!("Sesman Connector connected to {}", address),
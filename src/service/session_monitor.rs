@@ -0,0 +1,54 @@
+use crate::common::*;
+
+pub enum SessionMonitorEvent {
+    Created,
+    Destroyed,
+    PingFailed,
+}
+
+impl SessionMonitorEvent {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionMonitorEvent::Created => "created",
+            SessionMonitorEvent::Destroyed => "destroyed",
+            SessionMonitorEvent::PingFailed => "ping_failed",
+        }
+    }
+}
+
+pub struct SessionMonitor {
+    socket: zmq::Socket,
+}
+
+impl SessionMonitor {
+
+    pub fn new(context: &zmq::Context, path: &str, group: &str) -> Result<Self> {
+        let socket = context.socket(zmq::PUB)?;
+        socket.set_linger(0)?;
+
+        let address = format!("ipc://{}", path);
+        match socket.bind(address.as_str()) {
+            Ok(_) => debug!("Session monitor socket bound to {}", address),
+            Err(error) => return Err(RouterError::TransportError(format!("Failed to bind session monitor socket to {}: {}", address, error)))
+        }
+
+        // Same access restrictions as the admin socket
+        System::chmod(path, 0o660)?;
+        if let Err(error) = System::chgrp(path, group) {
+            warn!("Session monitor socket {} is only accessible by the router user: {}", path, error);
+        }
+
+        Ok(Self {
+            socket,
+        })
+    }
+
+    pub fn publish(&self, event: SessionMonitorEvent, session_id: &str, username: &str) {
+        // Events are sent as <timestamp>,<event>,<sessionId>,<username>
+        let message = format!("{},{},{},{}", System::current_time_s(), event.as_str(), session_id, username);
+        if let Err(error) = self.socket.send(message.as_str(), zmq::DONTWAIT) {
+            warn!("Failed to publish session {} event: {}", event.as_str(), error);
+        }
+    }
+}
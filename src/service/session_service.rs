@@ -1,60 +1,215 @@
 use crate::common::*;
-use crate::service::{EngineValidator, SesmanConnector};
+use crate::service::{EngineValidator, SesmanConnector, SessionStore, AuditLogger, AuditEvent, SocketPool, SessionMonitor, SessionMonitorEvent};
 
 use uuid::Uuid;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 use std::os::unix::io::{FromRawFd, IntoRawFd};
-use std::fs::File;
+use std::fs::{self, OpenOptions};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+// Fixed namespace for deterministic (v5) session ids
+const WEBX_NAMESPACE_UUID: Uuid = Uuid::from_bytes([
+    0x3c, 0x5e, 0x8a, 0x41, 0x9d, 0x27, 0x4b, 0x6f,
+    0xa1, 0x0e, 0x52, 0xd4, 0x7b, 0x93, 0xc8, 0x16,
+]);
+
+static SESSION_EXPIRY_WARNING_S: u64 = 300;
+static ENGINE_LOG_ROTATION_INTERVAL_S: u64 = 300;
+// User lookups can be slow (eg LDAP) so deleted users are only checked periodically
+static DELETED_USER_CHECK_INTERVAL_S: u64 = 60;
+static RECORDING_FRAME_RATE: &str = "10";
 
 pub struct SessionService {
     session_container: SessionContainer,
+    session_store: Option<SessionStore>,
+    metrics: Arc<Metrics>,
+    audit_logger: Option<AuditLogger>,
+    session_monitor: Option<SessionMonitor>,
+    // Session ids and usernames of the sessions already audited and published to the monitor
+    audited_sessions: HashMap<String, String>,
+    last_heartbeat_s: u64,
+    last_log_rotation_s: u64,
+    last_crash_check_s: u64,
+    last_deleted_user_check_s: u64,
+    socket_pool: SocketPool,
 }
 
 impl SessionService {
 
-    pub fn new() -> Self {
+    pub fn new(metrics: Arc<Metrics>, audit_logger: Option<AuditLogger>) -> Self {
         Self {
             session_container: SessionContainer::new(),
+            session_store: None,
+            metrics,
+            audit_logger,
+            session_monitor: None,
+            audited_sessions: HashMap::new(),
+            last_heartbeat_s: System::current_time_s(),
+            last_log_rotation_s: System::current_time_s(),
+            last_crash_check_s: System::current_time_s(),
+            last_deleted_user_check_s: System::current_time_s(),
+            socket_pool: SocketPool::new(),
         }
     }
 
-    pub fn stop_sessions(&mut self) {
-        self.session_container.stop_sessions();
+    pub fn set_session_monitor(&mut self, session_monitor: SessionMonitor) {
+        self.session_monitor = Some(session_monitor);
     }
 
-    pub fn get_or_create_session(&mut self, settings: &Settings, username: &str, password: &str, width: u32, height: u32, keyboard: &str, context: &zmq::Context) -> Result<&Session> {
+    pub fn stop_sessions(&mut self, settings: &Settings) {
+        self.run_pre_destroy_hook(settings, |_| true);
+        self.session_container.stop_sessions(settings.sesman.engine_stop_timeout());
+        self.on_sessions_changed();
+    }
+
+    pub fn restore_sessions(&mut self, session_store: SessionStore, settings: &Settings, context: &zmq::Context) {
+        match session_store.load() {
+            Ok(persisted_sessions) => {
+                for persisted_session in persisted_sessions {
+                    let mut session = persisted_session.into_session();
+
+                    // Only keep sessions whose engine is still running and responding, for users that are still authorized
+                    let result = self.validate_engine(session.engine(), settings, context, RetryPolicy::single_attempt())
+                        .and_then(|_| if settings.sesman.enabled { self.authorize_uid(session.uid(), settings) } else { Ok(()) });
+                    match result {
+                        Ok(_) => {
+                            // Keep track of the cgroup so that it is removed with the session
+                            if let Err(error) = SessionService::apply_resource_limits(&mut session, settings) {
+                                warn!("Failed to apply resource limits to restored session {}: {}", session.id(), error);
+                            }
+
+                            info!("Restored session {} on display {} for user \"{}\"", session.id(), session.display_id(), session.username());
+                            self.session_container.add_session(session);
+                        },
+                        Err(error) => {
+                            info!("Removing persisted session {} for user \"{}\": {}", session.id(), session.username(), error);
+                            if System::is_process_running_binary(session.engine().process_id(), &settings.engine.path) {
+                                session.stop(settings.sesman.engine_stop_timeout());
+                            }
+
+                            // Close X11 session
+                            if settings.sesman.enabled {
+                                self.request_session_logout(session.id(), context, settings);
+                            }
+                        }
+                    }
+                }
+            },
+            Err(error) => warn!("Failed to load persisted sessions from {}: {}", session_store.path(), error),
+        }
+
+        self.session_store = Some(session_store);
+        self.on_sessions_changed();
+    }
+
+    pub fn get_or_create_session(&mut self, settings: &Settings, request: &SessionRequest, source_ip: &str, context: &zmq::Context) -> Result<&Session> {
+        let username = request.username();
+
         // See if we are using the session manager
         let x11_session;
         if settings.sesman.enabled {
+            self.audit(AuditEvent::AuthAttempt { username, source_ip });
+
             // Request display/session Id from WebX Session Manager
-            x11_session = self.request_authenticated_x11_display(username, password, width, height, context, settings)?;
+            x11_session = match self.request_authenticated_x11_display(username, request.password(), request.resolution(), context, settings) {
+                Ok(x11_session) => x11_session,
+                Err(error) => {
+                    self.audit(AuditEvent::AuthFailure { username, reason: error.to_string() });
+                    return Err(error);
+                }
+            };
             debug!("Got response for session manager: user \"{}\" has display on \"{}\"", x11_session.username(), x11_session.display_id());
+
+            // Verify the user is authorized to use WebX sessions
+            if let Err(error) = self.authorize_user(x11_session.username(), settings) {
+                self.audit(AuditEvent::AuthFailure { username, reason: error.to_string() });
+                self.request_session_logout(x11_session.session_id(), context, settings);
+                return Err(error);
+            }
+
+            self.audit(AuditEvent::AuthSuccess { username, session_id: x11_session.session_id() });
         
         } else {
             x11_session = self.get_fallback_x11_display(settings)?;
         }
 
         // See if session already exists matching x11_session attributes
-        if self.session_container.get_session_by_x11session(&x11_session).is_none() {
-            // cleanup any other sessions for the user
-            self.session_container.remove_session_for_user(username);
+        let session_id = x11_session.session_id().to_string();
+        if let Some(session) = self.session_container.get_session_by_x11session(&x11_session) {
+            // Sessions are isolated by tenant
+            if session.tenant() != request.tenant() {
+                return Err(RouterError::SessionError(format!("Session for user \"{}\" belongs to another tenant", username)));
+            }
+
+        } else {
+            let max_sessions_per_user = settings.sesman.max_sessions_per_user();
+            if max_sessions_per_user <= 1 {
+                // cleanup any other sessions for the user
+                self.run_pre_destroy_hook(settings, |session| session.username() == username);
+                self.session_container.remove_session_for_user(username, settings.sesman.engine_stop_timeout());
+
+            } else if self.session_container.get_session_count_for_user(username) >= max_sessions_per_user as usize {
+                return Err(RouterError::SessionError(format!("User \"{}\" has reached the maximum of {} concurrent sessions", username, max_sessions_per_user)));
+            }
 
             // Create new session for the user
-            self.create_session(x11_session, settings, keyboard, context)?;
+            let result = self.create_session(x11_session, settings, request, context);
+            self.on_sessions_changed();
+            result?;
         } 
 
         // Return the session
-        return match self.session_container.get_session_by_username(username) {
+        return match self.session_container.get_session_by_session_id(&session_id) {
             Some(session) => Ok(session),
             None => Err(RouterError::SessionError(format!("Could not retrieve Session for user \"{}\"", username)))
         };
     }
 
-    pub fn ping_session(&mut self, session_id: &str, context: &zmq::Context) -> Result<()> {
+    pub fn get_session(&self, session_id: &str) -> Option<&Session> {
+        self.session_container.get_session_by_session_id(session_id)
+    }
+
+    pub fn get_sessions(&self) -> &[Session] {
+        self.session_container.get_sessions()
+    }
+
+    #[cfg(test)]
+    pub fn add_session(&mut self, session: Session) {
+        self.session_container.add_session(session);
+        self.on_sessions_changed();
+    }
+
+    pub fn notify_shutdown(&self, grace_period_s: u64, settings: &Settings, context: &zmq::Context) {
+        // Engines that do not support the command reply with an empty message
+        let engine_validator = EngineValidator::new(context.clone(), &self.socket_pool, &settings.engine, RetryPolicy::single_attempt());
+        let request = format!("shutting_down,{}", grace_period_s);
+        for session in self.session_container.get_sessions() {
+            if let Err(error) = engine_validator.send_request(session.engine().ipc(), &request) {
+                warn!("Failed to notify WebX Engine of session {} of the shutdown: {}", session.id(), error);
+            }
+        }
+    }
+
+    pub fn get_active_sessions(&self, within_s: u64) -> Vec<&Session> {
+        self.session_container.get_active_sessions(within_s)
+    }
+
+    pub fn ping_session(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<()> {
         if let Some(session) = self.session_container.get_session_by_session_id(session_id) {
-            if let Err(error) =  self.validate_engine(session.engine(), context, 1) {
+            if let Err(error) =  self.validate_engine(session.engine(), settings, context, RetryPolicy::single_attempt()) {
+                if let Some(session_monitor) = &self.session_monitor {
+                    session_monitor.publish(SessionMonitorEvent::PingFailed, session_id, session.username());
+                }
+
                 // Delete session
-                self.session_container.remove_session_with_id(session_id);
+                self.run_pre_destroy_hook(settings, |session| session.id() == session_id);
+                if let Err(remove_error) = self.session_container.remove_session_with_id(session_id, settings.sesman.engine_stop_timeout()) {
+                    warn!("Failed to remove session {}: {}", session_id, remove_error);
+                }
+                self.on_sessions_changed();
                 return Err(error);
             }
 
@@ -66,12 +221,182 @@ impl SessionService {
         Ok(())
     }
 
+    pub fn get_engine_status(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<EngineStatus> {
+        let session = match self.session_container.get_mut_session_by_session_id(session_id) {
+            Some(session) => session,
+            None => return Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        };
+
+        if !session.is_engine_running() {
+            return Ok(EngineStatus::Stopped);
+        }
+
+        // Unlike a ping, an unresponsive engine does not remove the session
+        let engine_validator = EngineValidator::new(context.clone(), &self.socket_pool, &settings.engine, RetryPolicy::single_attempt());
+        match engine_validator.validate_connection(session.engine().ipc()) {
+            Ok(_) => Ok(EngineStatus::Running),
+            Err(error) => {
+                debug!("WebX Engine of session {} is unresponsive: {}", session_id, error);
+                Ok(EngineStatus::Unresponsive)
+            }
+        }
+    }
+
+    pub fn delete_session(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<()> {
+        self.run_pre_destroy_hook(settings, |session| session.id() == session_id);
+        let result = self.session_container.remove_session_with_id(session_id, settings.sesman.engine_stop_timeout());
+        self.on_sessions_changed();
+
+        // Close X11 session (even if the session has already been removed)
+        if settings.sesman.enabled {
+            self.request_session_logout(session_id, context, settings);
+        }
+
+        result
+    }
+
+    pub fn resize_session(&mut self, session_id: &str, resolution: &ScreenResolution, settings: &Settings, context: &zmq::Context) -> Result<()> {
+        settings.sesman.validate_resolution(resolution)?;
+
+        let session = match self.session_container.get_mut_session_by_session_id(session_id) {
+            Some(session) => session,
+            None => return Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        };
+
+        // Ask the engine to prepare for the new resolution
+        let engine_validator = EngineValidator::new(context.clone(), &self.socket_pool, &settings.engine, RetryPolicy::single_attempt());
+        let response = engine_validator.send_request(session.engine().ipc(), &format!("resize,{},{}", resolution.width(), resolution.height()))?;
+        if response != "ok" {
+            return Err(RouterError::SessionError(format!("WebX Engine refused resize to {}: {}", resolution, response)));
+        }
+
+        // Change the resolution of the X11 display
+        let x11_session = session.x11_session();
+        let mut command = Command::new("xrandr");
+        command
+            .arg("--fb")
+            .arg(resolution.to_string())
+            .env("DISPLAY", x11_session.display_id());
+
+        if settings.sesman.enabled {
+            command.env("XAUTHORITY", x11_session.xauthority_file_path());
+        }
+
+        debug!("Running command: {}", format!("{:?}", command).replace("\"", ""));
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(RouterError::SessionError(format!("xrandr failed to resize display {}: {}", x11_session.display_id(), String::from_utf8_lossy(&output.stderr).trim())));
+        }
+
+        info!("Resized session {} to {}", session_id, resolution);
+        session.set_resolution(*resolution);
+        self.on_sessions_changed();
+
+        Ok(())
+    }
+
+    pub fn set_session_suspended(&mut self, session_id: &str, is_suspended: bool, settings: &Settings, context: &zmq::Context) -> Result<()> {
+        let session = match self.session_container.get_mut_session_by_session_id(session_id) {
+            Some(session) => session,
+            None => return Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        };
+
+        // The engine pauses or restarts its rendering
+        let request = if is_suspended { "suspend" } else { "resume" };
+        SessionService::send_engine_command(&self.socket_pool, session.engine(), request, settings, context)?;
+
+        info!("Session {} {}", session_id, if is_suspended { "suspended" } else { "resumed" });
+        session.set_suspended(is_suspended);
+
+        Ok(())
+    }
+
+    pub fn connect_session(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<u32> {
+        let session = match self.session_container.get_mut_session_by_session_id(session_id) {
+            Some(session) => session,
+            None => return Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        };
+
+        let max_connections = settings.sesman.max_connections_per_session();
+        if session.connection_count() >= max_connections {
+            return Err(RouterError::SessionError(format!("Session already has the maximum of {} connections", max_connections)));
+        }
+
+        SessionService::send_engine_command(&self.socket_pool, session.engine(), "connect", settings, context)?;
+        session.add_connection();
+
+        info!("Session {} now has {} connections", session_id, session.connection_count());
+        Ok(session.connection_count())
+    }
+
+    pub fn disconnect_session(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<u32> {
+        let session = match self.session_container.get_mut_session_by_session_id(session_id) {
+            Some(session) => session,
+            None => return Err(RouterError::SessionError(format!("Could not retrieve Session with ID \"{}\"", session_id)))
+        };
+
+        // The connection is removed even if the engine does not respond
+        session.remove_connection();
+        if let Err(error) = SessionService::send_engine_command(&self.socket_pool, session.engine(), "disconnect", settings, context) {
+            warn!("Failed to notify WebX Engine of disconnection from session {}: {}", session_id, error);
+        }
+
+        info!("Session {} now has {} connections", session_id, session.connection_count());
+        Ok(session.connection_count())
+    }
+
     pub fn update_session_activity(&mut self, session_id: &str) {
         if let Some(session) = self.session_container.get_mut_session_by_session_id(session_id) {
             session.update_activity();
         }
     }
 
+    pub fn ping_idle_sessions(&mut self, settings: &Settings, context: &zmq::Context) {
+        let heartbeat_interval_s = match settings.sesman.client_heartbeat_interval_s {
+            Some(heartbeat_interval_s) if heartbeat_interval_s > 0 => heartbeat_interval_s,
+            _ => return,
+        };
+
+        let current_time = System::current_time_s();
+        if current_time - self.last_heartbeat_s < heartbeat_interval_s {
+            return;
+        }
+        self.last_heartbeat_s = current_time;
+
+        // Sessions with instructions from the relay are known to be alive: only ping the idle ones
+        for (session_id, username) in self.session_container.get_inactive_session_ids(heartbeat_interval_s) {
+            trace!("Sending heartbeat ping to idle session {} for user {}", session_id, username);
+            if let Err(error) = self.ping_session(&session_id, settings, context) {
+                info!("Removed idle session {} for user {} after failed heartbeat ping: {}", session_id, username, error);
+            }
+        }
+    }
+
+    pub fn rotate_engine_logs(&mut self, settings: &Settings) {
+        let log_max_size_bytes = match settings.engine.log_max_size_bytes {
+            Some(log_max_size_bytes) if log_max_size_bytes > 0 => log_max_size_bytes,
+            _ => return,
+        };
+
+        let current_time = System::current_time_s();
+        if current_time - self.last_log_rotation_s < ENGINE_LOG_ROTATION_INTERVAL_S {
+            return;
+        }
+        self.last_log_rotation_s = current_time;
+
+        for session in self.session_container.get_sessions() {
+            for log_path in session.engine().log_paths() {
+                let is_rotation_required = fs::metadata(log_path).map_or(false, |metadata| metadata.len() > log_max_size_bytes);
+                if is_rotation_required {
+                    info!("Rotating WebX Engine log file {} of session {}", log_path, session.id());
+                    if let Err(error) = System::rotate_log_file(log_path, settings.engine.log_max_files()) {
+                        warn!("Failed to rotate WebX Engine log file {}: {}", log_path, error);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn cleanup_inactive_sessions(&mut self, settings: &Settings, context: &zmq::Context) {
         if settings.sesman.auto_logout_s > 0 {
             let inactive_sessions = self.session_container.get_inactive_session_ids(settings.sesman.auto_logout_s);
@@ -79,86 +404,387 @@ impl SessionService {
                 info!("Removing inactive session with id {} for user {}", &session.0, &session.1);
     
                 // Remove session
-                self.session_container.remove_session_with_id(&session.0);
+                self.run_pre_destroy_hook(settings, |inactive_session| inactive_session.id() == session.0);
+                if let Err(error) = self.session_container.remove_session_with_id(&session.0, settings.sesman.engine_stop_timeout()) {
+                    warn!("Failed to remove inactive session {}: {}", &session.0, error);
+                }
     
                 // Close X11 session
                 if settings.sesman.enabled {
                     self.request_session_logout(&session.0, context, settings);
                 }
             }
+
+            if !inactive_sessions.is_empty() {
+                self.on_sessions_changed();
+            }
         }
     }
 
-    fn create_session(&mut self, x11_session: X11Session, settings: &Settings, keyboard: &str, context: &zmq::Context)  -> Result<()> {
+    // Returns the ids of sessions that are about to reach their maximum duration
+    pub fn cleanup_expired_sessions(&mut self, settings: &Settings, context: &zmq::Context) -> Vec<String> {
+        let max_session_duration_s = settings.sesman.max_session_duration_s;
+        if max_session_duration_s == 0 {
+            return Vec::new();
+        }
+
+        for (session_id, username) in self.session_container.get_expired_session_ids(max_session_duration_s) {
+            info!("Removing session with id {} for user {}: maximum duration of {}s reached", session_id, username, max_session_duration_s);
+            if let Err(error) = self.delete_session(&session_id, settings, context) {
+                warn!("Failed to remove expired session {}: {}", session_id, error);
+            }
+        }
+
+        // Warn of sessions that expire soon
+        let warning_duration_s = max_session_duration_s.saturating_sub(SESSION_EXPIRY_WARNING_S);
+        let mut expiring_session_ids = Vec::new();
+        for (session_id, username) in self.session_container.get_expired_session_ids(warning_duration_s) {
+            if let Some(session) = self.session_container.get_mut_session_by_session_id(&session_id) {
+                if !session.is_expiry_warning_sent() {
+                    warn!("Session with id {} for user {} will expire in less than {}s", session_id, username, SESSION_EXPIRY_WARNING_S);
+                    session.set_expiry_warning_sent();
+                    expiring_session_ids.push(session_id);
+                }
+            }
+        }
+
+        expiring_session_ids
+    }
+
+    pub fn restart_crashed_engines(&mut self, settings: &Settings, context: &zmq::Context) {
+        // Checking every engine process on each event bus message is wasteful: crashes are detected periodically
+        let current_time = System::current_time_s();
+        if current_time - self.last_crash_check_s < settings.engine.restart_check_interval_s() {
+            return;
+        }
+        self.last_crash_check_s = current_time;
+
+        for session_id in self.session_container.get_crashed_session_ids() {
+            let _log_context = SessionLogContext::new(&session_id);
+            if let Err(error) = self.restart_engine(&session_id, settings, context) {
+                error!("Removing session {} after failing to restart its WebX Engine: {}", session_id, error);
+                if let Err(error) = self.delete_session(&session_id, settings, context) {
+                    warn!("Failed to remove session {}: {}", session_id, error);
+                }
+            }
+        }
+    }
+
+    pub fn cleanup_deleted_user_sessions(&mut self, settings: &Settings, context: &zmq::Context) {
+        if !settings.sesman.enabled {
+            return;
+        }
+
+        let current_time = System::current_time_s();
+        if current_time - self.last_deleted_user_check_s < DELETED_USER_CHECK_INTERVAL_S {
+            return;
+        }
+        self.last_deleted_user_check_s = current_time;
+
+        for uid in self.session_container.get_session_uids() {
+            match System::user_exists(uid) {
+                Ok(true) => {},
+                Ok(false) => {
+                    info!("Removing sessions for deleted user with uid {}", uid);
+                    self.run_pre_destroy_hook(settings, |session| session.uid() == uid);
+                    match self.session_container.remove_sessions_for_uid(uid, settings.sesman.engine_stop_timeout()) {
+                        Ok(session_ids) => {
+                            self.on_sessions_changed();
+
+                            // Close X11 sessions
+                            for session_id in session_ids.iter() {
+                                self.request_session_logout(session_id, context, settings);
+                            }
+                        },
+                        Err(error) => warn!("Failed to remove sessions for uid {}: {}", uid, error),
+                    }
+                },
+                Err(error) => warn!("Could not verify user with uid {}: {}", uid, error),
+            }
+        }
+    }
+
+    fn restart_engine(&mut self, session_id: &str, settings: &Settings, context: &zmq::Context) -> Result<()> {
+        let engine = match self.session_container.get_session_by_session_id(session_id) {
+            Some(session) => {
+                if session.engine_restarts() >= settings.engine.max_restarts() {
+                    return Err(RouterError::SessionError(format!("Maximum number of restarts ({}) reached", settings.engine.max_restarts())));
+                }
+
+                warn!("WebX Engine for user \"{}\" on display {} has stopped: restarting it", session.username(), session.display_id());
+                self.spawn_engine(session.x11_session(), settings, session.keyboard(), session.tenant(), true, session.cgroup())?
+            },
+            None => return Err(RouterError::SessionError(format!("Session not found with id {}", session_id)))
+        };
+
+        if let Some(session) = self.session_container.get_mut_session_by_session_id(session_id) {
+            session.replace_engine(engine);
+        }
+
+        // Validate that the new engine is running
+        let mut engine_version = None;
+        if let Some(session) = self.session_container.get_session_by_session_id(session_id) {
+            self.validate_engine(session.engine(), settings, context, settings.engine.retry_policy())?;
+            engine_version = self.get_engine_version(session.engine(), settings, context);
+        }
+
+        if let Some(session) = self.session_container.get_mut_session_by_session_id(session_id) {
+            session.engine_mut().set_version(engine_version);
+            info!("Restarted WebX Engine for user \"{}\" on display {} ({} restarts)", session.username(), session.display_id(), session.engine_restarts());
+        }
+
+        self.on_sessions_changed();
+
+        Ok(())
+    }
+
+    fn audit(&self, event: AuditEvent) {
+        if let Some(audit_logger) = &self.audit_logger {
+            audit_logger.log(event);
+        }
+    }
+
+    fn audit_session_changes(&mut self) {
+        if self.audit_logger.is_none() && self.session_monitor.is_none() {
+            return;
+        }
+
+        let audit_logger = &self.audit_logger;
+        let session_monitor = &self.session_monitor;
+        let sessions = self.session_container.get_sessions();
+
+        // Sessions that have been removed
+        self.audited_sessions.retain(|session_id, username| {
+            let exists = sessions.iter().any(|session| session.id() == session_id);
+            if !exists {
+                if let Some(audit_logger) = audit_logger {
+                    audit_logger.log(AuditEvent::SessionDestroyed { username: username.as_str(), session_id: session_id.as_str() });
+                }
+                if let Some(session_monitor) = session_monitor {
+                    session_monitor.publish(SessionMonitorEvent::Destroyed, session_id, username);
+                }
+            }
+            exists
+        });
+
+        // Sessions that have been created
+        for session in sessions {
+            if !self.audited_sessions.contains_key(session.id()) {
+                if let Some(audit_logger) = audit_logger {
+                    audit_logger.log(AuditEvent::SessionCreated { username: session.username(), session_id: session.id() });
+                }
+                if let Some(session_monitor) = session_monitor {
+                    session_monitor.publish(SessionMonitorEvent::Created, session.id(), session.username());
+                }
+                self.audited_sessions.insert(session.id().to_string(), session.username().to_string());
+            }
+        }
+    }
+
+    fn on_sessions_changed(&mut self) {
+        self.metrics.set_sessions_active(self.session_container.get_sessions().len());
+
+        // Close pooled sockets of engines that no longer exist
+        let engine_paths: Vec<&str> = self.session_container.get_sessions().iter().map(|session| session.engine().ipc()).collect();
+        self.socket_pool.retain_paths(&engine_paths);
+        self.audit_session_changes();
+
+        if let Some(session_store) = &self.session_store {
+            if let Err(error) = session_store.save(self.session_container.get_sessions()) {
+                error!("Failed to persist sessions to {}: {}", session_store.path(), error);
+            }
+        }
+    }
+
+    fn authorize_user(&self, username: &str, settings: &Settings) -> Result<()> {
+        if !SessionService::is_group_authorization_enabled(settings) {
+            return Ok(());
+        }
+
+        match Account::from_username(username) {
+            Some(account) => SessionService::authorize_account(&account, settings),
+            None => Err(RouterError::AuthenticationError(format!("Could not get account for user {}", username)))
+        }
+    }
+
+    fn authorize_uid(&self, uid: u32, settings: &Settings) -> Result<()> {
+        if !SessionService::is_group_authorization_enabled(settings) {
+            return Ok(());
+        }
+
+        match Account::from_uid(uid) {
+            Some(account) => SessionService::authorize_account(&account, settings),
+            None => Err(RouterError::AuthenticationError(format!("Could not get account for uid {}", uid)))
+        }
+    }
+
+    fn is_group_authorization_enabled(settings: &Settings) -> bool {
+        let required_groups = settings.sesman.required_groups.as_deref().unwrap_or_default();
+        let excluded_groups = settings.sesman.excluded_groups.as_deref().unwrap_or_default();
+        !required_groups.is_empty() || !excluded_groups.is_empty()
+    }
+
+    fn authorize_account(account: &Account, settings: &Settings) -> Result<()> {
+        let required_groups = settings.sesman.required_groups.as_deref().unwrap_or_default();
+        let excluded_groups = settings.sesman.excluded_groups.as_deref().unwrap_or_default();
+
+        if !required_groups.is_empty() && !account.is_member_of_any(required_groups) {
+            warn!("User {} is not a member of any of the required groups", account.username());
+            return Err(RouterError::AuthenticationError("User not authorized for WebX sessions".to_string()));
+        }
+
+        if account.is_member_of_any(excluded_groups) {
+            warn!("User {} is a member of an excluded group", account.username());
+            return Err(RouterError::AuthenticationError("User not authorized for WebX sessions".to_string()));
+        }
+
+        debug!("User {} (uid {}) is authorized for WebX sessions", account.username(), account.uid());
+        Ok(())
+    }
+
+    fn create_session(&mut self, x11_session: X11Session, settings: &Settings, request: &SessionRequest, context: &zmq::Context)  -> Result<()> {
+        let _log_context = SessionLogContext::new(x11_session.session_id());
         debug!("Creating session for user \"{}\" on display {}", &x11_session.username(), &x11_session.display_id());
 
+        // Run the site-specific pre-creation hook: a failure aborts the session creation
+        if let Some(pre_create) = settings.engine.hooks.as_ref().and_then(|hooks| hooks.pre_create.as_ref()) {
+            self.run_hook("pre_create", pre_create, &x11_session)?;
+        }
+
+        // The cgroup is created first so that the WebX Engine is limited from the start
+        let cgroup = match SessionService::create_cgroup(x11_session.session_id(), settings) {
+            Ok(cgroup) => cgroup,
+            Err(error) => return Err(RouterError::SessionError(format!("Failed to apply resource limits to session for user {}: {}", x11_session.username(), error)))
+        };
+
         // Spawn a new WebX Engine
-        let engine = self.spawn_engine(&x11_session, settings, keyboard)?;
+        let engine = match self.spawn_engine(&x11_session, settings, request.keyboard(), request.tenant(), false, cgroup.as_ref()) {
+            Ok(engine) => engine,
+            Err(error) => {
+                if let Some(cgroup) = cgroup {
+                    cgroup.remove();
+                }
+                return Err(error);
+            }
+        };
 
-        let mut session = Session::new(x11_session, engine);
+        let mut session = Session::new(x11_session, engine, *request.resolution(), request.keyboard(), request.tenant());
+        if let Some(cgroup) = cgroup {
+            session.set_cgroup(cgroup);
+        }
 
         // Validate that the engine is running
-        if let Err(error) = self.validate_engine(session.engine(), context, 3) {
+        if let Err(error) = self.validate_engine(session.engine(), settings, context, settings.engine.retry_policy()) {
             // Make sure the engine process has stopped
-            session.stop();
+            session.stop(settings.sesman.engine_stop_timeout());
             return Err(RouterError::SessionError(format!("Failed to validate that WebX Engine is running for user {}: {}", session.username(), error)));
         }
 
+        let engine_version = self.get_engine_version(session.engine(), settings, context);
+        session.engine_mut().set_version(engine_version);
+
+        // Sessions must not run unrecorded when recording is required
+        if let Some(recording_path) = settings.sesman.recording_path() {
+            match self.spawn_recording(session.x11_session(), session.resolution(), recording_path, settings) {
+                Ok(recording) => session.set_recording(recording),
+                Err(error) => {
+                    session.stop(settings.sesman.engine_stop_timeout());
+                    return Err(RouterError::SessionError(format!("Failed to start recording of session for user {}: {}", session.username(), error)));
+                }
+            }
+        }
+
+        if let Some(post_create) = settings.engine.hooks.as_ref().and_then(|hooks| hooks.post_create.as_ref()) {
+            if let Err(error) = self.run_hook("post_create", post_create, session.x11_session()) {
+                warn!("{}", error);
+            }
+        }
+
         debug!("Created session {} on display {} for user \"{}\"", &session.id(), &session.display_id(), &session.username());
 
         // Store session
         self.session_container.add_session(session);
+        self.metrics.inc_sessions_created();
 
         Ok(())
     }
 
     fn get_fallback_x11_display(&self, settings: &Settings) -> Result<X11Session> {
-        let session_id = Uuid::new_v4().to_simple().to_string();
-        let username = System::get_current_username()?;
         let display = &settings.sesman.fallback_display_id;
-        Ok(X11Session::new(session_id, username, display.to_string(), "".to_string()))
+
+        // Reuse the session already running on the fallback display
+        if let Some(session) = self.session_container.get_session_by_display(display) {
+            let x11_session = session.x11_session();
+            return Ok(X11Session::new(x11_session.session_id().to_string(), x11_session.username().to_string(), x11_session.uid(), display.to_string(), "".to_string()));
+        }
+
+        let session_id = if settings.sesman.deterministic_session_ids {
+            SessionService::deterministic_session_id(display)
+
+        } else {
+            Uuid::new_v4().to_simple().to_string()
+        };
+        let username = System::get_current_username()?;
+        let uid = System::get_current_uid();
+        Ok(X11Session::new(session_id, username, uid, display.to_string(), "".to_string()))
+    }
+
+    fn deterministic_session_id(display_id: &str) -> String {
+        // The same display always gives the same session id within the hour, simplifying log correlation.
+        // The hourly salt avoids reusing ids across restarts but means that ids are predictable from the display:
+        // the session id must not be relied on as a secret when this is enabled
+        let hour = System::current_time_s() / 3600;
+        let name = format!("{}:{}", display_id, hour);
+        Uuid::new_v5(&WEBX_NAMESPACE_UUID, name.as_bytes()).to_simple().to_string()
     }
 
-    fn request_authenticated_x11_display(&self, username: &str, password: &str, width: u32, height: u32, context: &zmq::Context, settings: &Settings) -> Result<X11Session> {
+    fn request_authenticated_x11_display(&self, username: &str, password: &str, resolution: &ScreenResolution, context: &zmq::Context, settings: &Settings) -> Result<X11Session> {
         // Call to WebX Session Manager
-        let sesman_connector = SesmanConnector::new(context.clone());
+        let sesman_connector = SesmanConnector::new(context.clone(), settings.sesman.authentication_timeout_s());
 
-        sesman_connector.get_authenticated_x11_session(username, password, width, height, &settings.transport.ipc.sesman_connector)
+        sesman_connector.get_authenticated_x11_session(username, password, resolution.width(), resolution.height(), &settings.transport.ipc.sesman_connector)
     }
 
     fn request_session_logout(&self, session_id: &str, context: &zmq::Context, settings: &Settings) {
         // Call to WebX Session Manager
-        let sesman_connector = SesmanConnector::new(context.clone());
+        let sesman_connector = SesmanConnector::new(context.clone(), settings.sesman.authentication_timeout_s());
 
         if let Err(error) = sesman_connector.logout(session_id, &settings.transport.ipc.sesman_connector) {
             warn!("Got error logging out X11 session: {}", error);
         }
     }
 
-    fn spawn_engine(&self, x11_session: &X11Session, settings: &Settings, keyboard: &str) -> Result<Engine> {
-        let engine_path = &settings.engine.path;
-        let engine_logdir = &settings.engine.logdir;
+    fn spawn_engine(&self, x11_session: &X11Session, settings: &Settings, keyboard: &str, tenant: &str, is_restart: bool, cgroup: Option<&CgroupV2>) -> Result<Engine> {
+        let engine_settings = &settings.engine;
+        let engine_path = &engine_settings.path;
         let message_proxy_path = &settings.transport.ipc.message_proxy;
         let instruction_proxy_path = &settings.transport.ipc.instruction_proxy;
         let engine_connector_root_path = &settings.transport.ipc.engine_connector_root;
 
         // Get engine log path
         let log_path: String;
-        if settings.sesman.enabled {
-            log_path = format!("{}/webx-engine.{}.log", engine_logdir, x11_session.session_id());
+        if settings.sesman.enabled || engine_settings.log_path_template.is_some() {
+            log_path = engine_settings.resolve_log_path(engine_settings.log_path_template(), x11_session.session_id(), x11_session.username());
         
         } else {
-            log_path = format!("{}/webx-engine.log", engine_logdir);
+            log_path = format!("{}/webx-engine.log", engine_settings.logdir);
         }
 
-        let file = File::create(log_path)?;
-        let file_descriptor = file.into_raw_fd();
-        let file_out = unsafe { Stdio::from_raw_fd(file_descriptor) };
+        let file_out = self.create_log_file(&log_path, is_restart)?;
+        let mut log_paths = vec![log_path];
 
         // Get engine connector IPC path
         let session_connector_path = format!("{}.{}.ipc", engine_connector_root_path, x11_session.session_id());
 
         let mut command = Command::new(engine_path);
+
+        // Only pass on the allowed router environment variables
+        if !engine_settings.env_allowlist.is_empty() || !engine_settings.env_blocklist.is_empty() {
+            command
+                .env_clear()
+                .envs(std::env::vars().filter(|(name, _)| engine_settings.is_environment_variable_allowed(name)));
+        }
+
         command
             .arg("-k")
             .arg(keyboard)
@@ -170,6 +796,17 @@ impl SessionService {
             .env("WEBX_ENGINE_IPC_INSTRUCTION_PROXY_PATH", instruction_proxy_path)
             .env("WEBX_ENGINE_SESSION_ID", x11_session.session_id());
 
+        if !tenant.is_empty() {
+            command.env("WEBX_ENGINE_TENANT_ID", tenant);
+        }
+
+        // Redirect stderr to a separate log file if required
+        if let Some(stderr_path_template) = &engine_settings.stderr_path_template {
+            let stderr_path = engine_settings.resolve_log_path(stderr_path_template, x11_session.session_id(), x11_session.username());
+            command.stderr(self.create_log_file(&stderr_path, is_restart)?);
+            log_paths.push(stderr_path);
+        }
+
         if settings.sesman.enabled {
             debug!("Launching WebX Engine \"{}\" on display {}", engine_path, x11_session.display_id());
             command
@@ -179,28 +816,166 @@ impl SessionService {
             debug!("Launching WebX Engine \"{}\" on display {}", engine_path, x11_session.display_id());
         }
 
+        if let Some(cgroup) = cgroup {
+            cgroup.add_command(&mut command)?;
+        }
+
         debug!("Spawning command: {}", format!("{:?}", command).replace("\"", ""));
 
-        match command.spawn() {
+        match System::spawn_with_context(&mut command, engine_path) {
             Err(error) => Err(RouterError::SessionError(format!("Failed to spawn WebX Engine: {}", error))),
-            Ok(child) => Ok(Engine::new(child, session_connector_path))
+            Ok(child) => Ok(Engine::new(child, session_connector_path, log_paths))
         }
     }
 
-    fn validate_engine(&self, engine: &Engine, context: &zmq::Context, mut tries: i32) -> Result<()> {
-        // Verify session is running
-        let engine_validator = EngineValidator::new(context.clone());
-        let mut connection_error = "".to_string();
-        while tries > 0 {
-            match engine_validator.validate_connection(&engine.ipc()) {
-                Ok(_) => return Ok(()),
-                Err(error) => {
-                    connection_error = error.to_string();
-                    tries -= 1;
+    fn create_cgroup(session_id: &str, settings: &Settings) -> Result<Option<CgroupV2>> {
+        match &settings.sesman.resource_limits {
+            Some(resource_limits) => Ok(Some(CgroupV2::create(&format!("webx-{}", session_id), resource_limits)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn apply_resource_limits(session: &mut Session, settings: &Settings) -> Result<()> {
+        // Restored WebX Engines are already running so they are moved to the cgroup
+        if let Some(cgroup) = SessionService::create_cgroup(session.id(), settings)? {
+            let result = cgroup.add_process(session.engine().process_id());
+
+            // The cgroup is removed when the session is stopped, even if the process could not be added
+            session.set_cgroup(cgroup);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    fn spawn_recording(&self, x11_session: &X11Session, resolution: &ScreenResolution, recording_path: &str, settings: &Settings) -> Result<Child> {
+        System::create_dir(Path::new(recording_path), 0o700)?;
+
+        // Include the start time so that recordings of restarted sessions are not overwritten
+        let output_path = format!("{}/{}-{}.mp4", recording_path, x11_session.session_id(), System::current_time_s());
+
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-loglevel", "error", "-f", "x11grab", "-r", RECORDING_FRAME_RATE])
+            .arg("-s")
+            .arg(resolution.to_string())
+            .arg("-i")
+            .arg(x11_session.display_id())
+            .args(["-codec:v", "libx264"])
+            .arg(&output_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .env("DISPLAY", x11_session.display_id());
+
+        if settings.sesman.enabled {
+            command.env("XAUTHORITY", x11_session.xauthority_file_path());
+        }
+
+        debug!("Spawning command: {}", format!("{:?}", command).replace("\"", ""));
+        let recording = System::spawn_with_context(&mut command, "ffmpeg")?;
+        info!("Recording session {} to {}", x11_session.session_id(), output_path);
+
+        Ok(recording)
+    }
+
+    fn create_log_file(&self, log_path: &str, is_restart: bool) -> Result<Stdio> {
+        // Make sure the log directory exists
+        if let Some(log_dir) = Path::new(log_path).parent() {
+            System::create_dir(log_dir, 0o700)?;
+        }
+
+        // Append mode makes the engine keep writing at the end of the file once the log rotation has truncated it
+        let file = OpenOptions::new().create(true).append(true).open(log_path)?;
+
+        // Keep the output of an engine that stopped unexpectedly when it is restarted
+        if !is_restart {
+            file.set_len(0)?;
+        }
+        let file_descriptor = file.into_raw_fd();
+        let file_out = unsafe { Stdio::from_raw_fd(file_descriptor) };
+
+        Ok(file_out)
+    }
+
+    fn run_pre_destroy_hook<P: Fn(&Session) -> bool>(&self, settings: &Settings, predicate: P) {
+        if let Some(pre_destroy) = settings.engine.hooks.as_ref().and_then(|hooks| hooks.pre_destroy.as_ref()) {
+            for session in self.session_container.get_sessions().iter().filter(|session| predicate(session)) {
+                if let Err(error) = self.run_hook("pre_destroy", pre_destroy, session.x11_session()) {
+                    warn!("{}", error);
                 }
             }
         }
-        Err(RouterError::SessionError(connection_error))
     }
 
-}
\ No newline at end of file
+    fn run_hook(&self, name: &str, hook: &str, x11_session: &X11Session) -> Result<()> {
+        debug!("Running {} hook {} for session {}", name, hook, x11_session.session_id());
+        let status = Command::new(hook)
+            .env("WEBX_SESSION_USERNAME", x11_session.username())
+            .env("WEBX_SESSION_ID", x11_session.session_id())
+            .env("WEBX_SESSION_DISPLAY", x11_session.display_id())
+            .status()
+            .map_err(|error| RouterError::SessionError(format!("Failed to run {} hook {}: {}", name, hook, error)))?;
+
+        if !status.success() {
+            return Err(RouterError::SessionError(format!("The {} hook {} failed with {}", name, hook, status)));
+        }
+
+        Ok(())
+    }
+
+    fn get_engine_version(&self, engine: &Engine, settings: &Settings, context: &zmq::Context) -> Option<String> {
+        let engine_validator = EngineValidator::new(context.clone(), &self.socket_pool, &settings.engine, RetryPolicy::single_attempt());
+        match engine_validator.send_request(engine.ipc(), "version") {
+            // Older engines reply with an empty message to unknown commands
+            Ok(version) if !version.is_empty() => Some(version),
+            Ok(_) => None,
+            Err(error) => {
+                warn!("Failed to get WebX Engine version: {}", error);
+                None
+            }
+        }
+    }
+
+    fn send_engine_command(socket_pool: &SocketPool, engine: &Engine, request: &str, settings: &Settings, context: &zmq::Context) -> Result<()> {
+        let engine_validator = EngineValidator::new(context.clone(), socket_pool, &settings.engine, RetryPolicy::single_attempt());
+        let response = engine_validator.send_request(engine.ipc(), request)?;
+        if response != "ok" {
+            return Err(RouterError::SessionError(format!("WebX Engine refused {} request: {}", request, response)));
+        }
+
+        Ok(())
+    }
+
+    fn validate_engine(&self, engine: &Engine, settings: &Settings, context: &zmq::Context, retry_policy: RetryPolicy) -> Result<()> {
+        // Verify session is running
+        let engine_validator = EngineValidator::new(context.clone(), &self.socket_pool, &settings.engine, retry_policy);
+        let start_time = Instant::now();
+        engine_validator.validate_connection(&engine.ipc())?;
+        self.metrics.observe_engine_ping_latency_ms(start_time.elapsed().as_secs_f64() * 1000.0);
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn engine_log_is_only_truncated_for_new_engines() {
+        let service = SessionService::new(Arc::new(Metrics::new()), None);
+        let log_path = env::temp_dir().join(format!("webx-router-test-{}.log", process::id())).to_string_lossy().to_string();
+        fs::write(&log_path, "engine crashed\n").unwrap();
+
+        service.create_log_file(&log_path, true).unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "engine crashed\n");
+
+        service.create_log_file(&log_path, false).unwrap();
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "");
+
+        fs::remove_file(&log_path).unwrap();
+    }
+}
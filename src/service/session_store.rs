@@ -0,0 +1,111 @@
+use crate::common::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+pub struct PersistedSession {
+    session_id: String,
+    username: String,
+    uid: u32,
+    display_id: String,
+    xauthority_file_path: String,
+    engine_process_id: u32,
+    engine_ipc: String,
+    #[serde(default)]
+    engine_log_paths: Vec<String>,
+    #[serde(default)]
+    engine_version: Option<String>,
+    width: u32,
+    height: u32,
+    keyboard: String,
+    #[serde(default)]
+    tenant: String,
+    created_at: u64,
+}
+
+impl PersistedSession {
+
+    pub fn from_session(session: &Session) -> Self {
+        Self {
+            session_id: session.id().to_string(),
+            username: session.username().to_string(),
+            uid: session.uid(),
+            display_id: session.display_id().to_string(),
+            xauthority_file_path: session.x11_session().xauthority_file_path().to_string(),
+            engine_process_id: session.engine().process_id(),
+            engine_ipc: session.engine().ipc().to_string(),
+            engine_log_paths: session.engine().log_paths().to_vec(),
+            engine_version: session.engine().version().map(|version| version.to_string()),
+            width: session.resolution().width(),
+            height: session.resolution().height(),
+            keyboard: session.keyboard().to_string(),
+            tenant: session.tenant().to_string(),
+            created_at: session.created_at(),
+        }
+    }
+
+    pub fn into_session(self) -> Session {
+        let x11_session = X11Session::new(self.session_id, self.username, self.uid, self.display_id, self.xauthority_file_path);
+        let mut engine = Engine::recovered(self.engine_process_id, self.engine_ipc, self.engine_log_paths);
+        engine.set_version(self.engine_version);
+        let mut session = Session::new(x11_session, engine, ScreenResolution::new(self.width, self.height), &self.keyboard, &self.tenant);
+        session.set_created_at(self.created_at);
+        session
+    }
+}
+
+pub struct SessionStore {
+    path: String,
+}
+
+impl SessionStore {
+
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        return &self.path;
+    }
+
+    pub fn load(&self) -> Result<Vec<PersistedSession>> {
+        if !Path::new(&self.path).exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+        let sessions = serde_json::from_str::<Vec<PersistedSession>>(&content)?;
+
+        Ok(sessions)
+    }
+
+    pub fn save(&self, sessions: &[Session]) -> Result<()> {
+        let persisted_sessions: Vec<PersistedSession> = sessions.iter().map(PersistedSession::from_session).collect();
+        let content = serde_json::to_string(&persisted_sessions)?;
+
+        if let Some(dir) = Path::new(&self.path).parent() {
+            System::create_dir(dir, 0o700)?;
+        }
+
+        // Write to a temporary file first so that the store is never left partially written.
+        // The file contains session ids so it is only readable by the router user
+        let temporary_path = format!("{}.tmp", self.path);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temporary_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary_path, &self.path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// Connected REQ sockets to the WebX Engines, kept between requests to avoid reconnecting each time
+pub struct SocketPool {
+    sockets: RefCell<HashMap<String, Vec<zmq::Socket>>>,
+}
+
+impl SocketPool {
+
+    pub fn new() -> Self {
+        Self {
+            sockets: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn take(&self, path: &str) -> Option<zmq::Socket> {
+        self.sockets.borrow_mut().get_mut(path).and_then(|sockets| sockets.pop())
+    }
+
+    pub fn put(&self, path: &str, socket: zmq::Socket, pool_size: usize) {
+        // Sockets beyond the pool size are closed when dropped
+        let mut sockets = self.sockets.borrow_mut();
+        let path_sockets = sockets.entry(path.to_string()).or_insert_with(Vec::new);
+        if path_sockets.len() < pool_size {
+            path_sockets.push(socket);
+        }
+    }
+
+    pub fn retain_paths(&self, paths: &[&str]) {
+        self.sockets.borrow_mut().retain(|path, _| paths.contains(&path.as_str()));
+    }
+}
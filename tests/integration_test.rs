@@ -0,0 +1,123 @@
+use webx_router::common::{Metrics, RetryPolicy, RouterError, ScreenResolution, SessionRequest, Settings};
+use webx_router::service::{EngineValidator, SessionService, SocketPool};
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn create_settings(name: &str) -> Settings {
+    // Engines are started on the fallback display with the mock engine binary, without the WebX Session Manager
+    let root_path = env::temp_dir().join(format!("webx-router-test-{}-{}", name, process::id())).to_string_lossy().to_string();
+    let mut settings = Settings::new("config.yml").unwrap();
+    settings.sesman.enabled = false;
+    settings.engine.path = env!("CARGO_BIN_EXE_webx-mock-engine").to_string();
+    settings.engine.logdir = format!("{}-logs", root_path);
+    settings.transport.ipc.engine_connector_root = root_path;
+    settings
+}
+
+fn create_request() -> SessionRequest {
+    SessionRequest::new("test".to_string(), "".to_string(), ScreenResolution::new(1024, 768), "gb".to_string(), String::new())
+}
+
+#[test]
+fn session_creation_spawns_and_validates_engine() {
+    let settings = create_settings("create");
+    let context = zmq::Context::new();
+    let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+
+    let session_id = service.get_or_create_session(&settings, &create_request(), "localhost", &context)
+        .map(|session| session.id().to_string())
+        .unwrap();
+
+    // The version is requested from the engine once it has been validated
+    let session = service.get_session(&session_id).unwrap();
+    assert_eq!(session.engine().version(), Some("0.1.0-mock"));
+    let ipc_path = session.engine().ipc().to_string();
+    assert!(Path::new(&ipc_path).exists());
+
+    assert!(service.ping_session(&session_id, &settings, &context).is_ok());
+
+    service.delete_session(&session_id, &settings, &context).unwrap();
+    assert!(service.get_session(&session_id).is_none());
+    assert!(!Path::new(&ipc_path).exists());
+}
+
+#[test]
+fn engine_commands_are_sent_to_engine() {
+    let settings = create_settings("commands");
+    let context = zmq::Context::new();
+    let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+
+    let session_id = service.get_or_create_session(&settings, &create_request(), "localhost", &context)
+        .map(|session| session.id().to_string())
+        .unwrap();
+
+    assert!(service.set_session_suspended(&session_id, true, &settings, &context).is_ok());
+    assert!(service.get_session(&session_id).unwrap().is_suspended());
+    assert!(service.set_session_suspended(&session_id, false, &settings, &context).is_ok());
+
+    assert_eq!(service.connect_session(&session_id, &settings, &context).unwrap(), 1);
+    assert_eq!(service.disconnect_session(&session_id, &settings, &context).unwrap(), 0);
+
+    service.delete_session(&session_id, &settings, &context).unwrap();
+}
+
+#[test]
+fn failed_ping_removes_session() {
+    let settings = create_settings("ping");
+    let context = zmq::Context::new();
+    let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+
+    let session_id = service.get_or_create_session(&settings, &create_request(), "localhost", &context)
+        .map(|session| session.id().to_string())
+        .unwrap();
+
+    // Stop the engine behind the router's back (the mock engine removes its IPC socket file when it exits)
+    let session = service.get_session(&session_id).unwrap();
+    let ipc_path = session.engine().ipc().to_string();
+    kill(Pid::from_raw(session.engine().process_id() as i32), Signal::SIGTERM).unwrap();
+    for _ in 0..50 {
+        if !Path::new(&ipc_path).exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    assert!(service.ping_session(&session_id, &settings, &context).is_err());
+    assert!(service.get_session(&session_id).is_none());
+}
+
+#[test]
+fn oversized_engine_response_is_rejected() {
+    let mut settings = create_settings("oversized");
+    settings.engine.max_response_size_bytes = Some(1024);
+    let context = zmq::Context::new();
+    let mut service = SessionService::new(Arc::new(Metrics::new()), None);
+
+    let session_id = service.get_or_create_session(&settings, &create_request(), "localhost", &context)
+        .map(|session| session.id().to_string())
+        .unwrap();
+    let ipc_path = service.get_session(&session_id).unwrap().engine().ipc().to_string();
+
+    let socket_pool = SocketPool::new();
+    let engine_validator = EngineValidator::new(context.clone(), &socket_pool, &settings.engine, RetryPolicy::single_attempt());
+
+    // Sockets are returned to the pool after a valid response
+    assert_eq!(engine_validator.send_request(&ipc_path, "ping").unwrap(), "pong");
+    assert!(socket_pool.take(&ipc_path).is_some());
+
+    match engine_validator.send_request(&ipc_path, "oversized") {
+        Err(RouterError::TransportError(message)) => assert!(message.contains("response is too large"), "unexpected error {}", message),
+        Err(error) => panic!("unexpected error {}", error),
+        Ok(_) => panic!("oversized response was accepted"),
+    }
+    assert!(socket_pool.take(&ipc_path).is_none());
+
+    service.delete_session(&session_id, &settings, &context).unwrap();
+}
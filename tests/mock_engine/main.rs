@@ -0,0 +1,61 @@
+// Mock WebX Engine used by the integration tests.
+//
+// Listens on the IPC path given by WEBX_ENGINE_IPC_SESSION_CONNECTOR_PATH and answers the engine
+// requests made by the WebX Router. Exits cleanly (removing the IPC socket file) on SIGINT or SIGTERM.
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Larger than the maximum response size used by the tests
+static OVERSIZED_RESPONSE_BYTES: usize = 4096;
+
+static IS_RUNNING: AtomicBool = AtomicBool::new(true);
+
+extern "C" fn handle_signal(_: i32) {
+    IS_RUNNING.store(false, Ordering::SeqCst);
+}
+
+fn main() {
+    let path = match env::var("WEBX_ENGINE_IPC_SESSION_CONNECTOR_PATH") {
+        Ok(path) => path,
+        Err(_) => {
+            eprintln!("WEBX_ENGINE_IPC_SESSION_CONNECTOR_PATH is not set");
+            process::exit(1);
+        }
+    };
+
+    let action = SigAction::new(SigHandler::Handler(handle_signal), SaFlags::empty(), SigSet::empty());
+    unsafe {
+        sigaction(Signal::SIGINT, &action).expect("Failed to set SIGINT handler");
+        sigaction(Signal::SIGTERM, &action).expect("Failed to set SIGTERM handler");
+    }
+
+    let context = zmq::Context::new();
+    let socket = context.socket(zmq::REP).expect("Failed to create REP socket");
+    socket.set_linger(0).unwrap();
+    socket.bind(&format!("ipc://{}", path)).expect("Failed to bind REP socket");
+
+    let mut items = [socket.as_poll_item(zmq::POLLIN)];
+    while IS_RUNNING.load(Ordering::SeqCst) {
+        // Poll with a timeout so that signals are handled
+        if zmq::poll(&mut items, 100).is_ok() && items[0].is_readable() {
+            let mut msg = zmq::Message::new();
+            if socket.recv(&mut msg, 0).is_ok() {
+                let response = match msg.as_str().unwrap_or("") {
+                    "ping" => "pong".to_string(),
+                    "capabilities" => "resize=true".to_string(),
+                    "version" => "0.1.0-mock".to_string(),
+                    "oversized" => "x".repeat(OVERSIZED_RESPONSE_BYTES),
+                    request if request.starts_with("resize,") => "ok".to_string(),
+                    "suspend" | "resume" | "connect" | "disconnect" => "ok".to_string(),
+                    _ => String::new(),
+                };
+                socket.send(response.as_str(), 0).unwrap();
+            }
+        }
+    }
+
+    let _ = fs::remove_file(&path);
+}